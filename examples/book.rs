@@ -0,0 +1,19 @@
+//! Build an opening book for given board (standard by default) with alpha - beta and save it.
+use blobwar::board::Board;
+use blobwar::configuration::Configuration;
+use blobwar::evaluation::Material;
+use blobwar::strategy::{AlphaBeta, Book};
+use std::env::args;
+
+fn main() {
+    let name = args().nth(1).unwrap_or_else(|| "standard".to_owned());
+    let plies = args()
+        .nth(2)
+        .map_or(4, |p| p.parse().expect("invalid number of plies"));
+    let board = Board::load(&name).expect("failed loading board");
+    let start = Configuration::new(&board);
+    let book = Book::generate(&start, plies, &mut AlphaBeta(4, Material));
+    let path = format!("{}.book.json", name);
+    book.save(&path).expect("failed saving book");
+    println!("{} positions saved into {}", book.len(), path);
+}
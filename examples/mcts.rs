@@ -0,0 +1,15 @@
+//! Play a sampling-based player against alpha - beta on given board (irregular by default).
+use blobwar::board::Board;
+use blobwar::configuration::Configuration;
+use blobwar::evaluation::Material;
+use blobwar::strategy::{AlphaBeta, Mcts, Playout};
+use std::env::args;
+
+fn main() {
+    let name = args().nth(1).unwrap_or_else(|| "irregular".to_owned());
+    let duration = args().nth(2).map_or(1000, |d| d.parse().expect("invalid duration"));
+    let board = Board::load(&name).expect("failed loading board");
+    let mut game = Configuration::new(&board);
+    let mcts = Mcts::new().playout(Playout::Greedy).duration(duration);
+    game.battle(mcts, AlphaBeta(4, Material)); // red, blue
+}
@@ -0,0 +1,40 @@
+//! Measure how much move ordering helps alpha - beta pruning.
+use blobwar::configuration::Configuration;
+use blobwar::evaluation::Material;
+use blobwar::strategy::{AlphaBeta, MoveOrdering, SearchContext, Strategy, TranspositionTable};
+use std::env::args;
+
+fn main() {
+    let depth = args().nth(1).map_or(5, |d| d.parse().expect("invalid depth"));
+    let board = Default::default();
+    let mut state = Configuration::new(&board);
+    for _ in 0..6 {
+        // play a few greedy-ish moves to get away from the (symmetric) start
+        let movement = state.movements().max_by_key(|m| state.converted_blobs(m));
+        match movement {
+            Some(movement) => state.apply_movement(&movement),
+            None => state.apply_skip(),
+        };
+    }
+    println!("{}", state);
+    report("unordered", &state, depth, MoveOrdering::disabled);
+    report("ordered", &state, depth, MoveOrdering::default);
+}
+
+fn report<F: Fn() -> MoveOrdering>(name: &str, state: &Configuration, depth: u8, ordering: F) {
+    let table = TranspositionTable::default();
+    let mut context = SearchContext::default()
+        .with_table(&table)
+        .with_ordering(ordering());
+    let movement = AlphaBeta(depth, Material).compute_next_move(state, &mut context);
+    let stats = context.stats();
+    println!(
+        "{}: {} in {:?}, {} nodes, {} cutoffs ({} on first move)",
+        name,
+        movement.map_or("pass".to_owned(), |m| m.to_string()),
+        context.elapsed(),
+        stats.nodes,
+        stats.cutoffs,
+        stats.first_move_cutoffs
+    );
+}
@@ -0,0 +1,36 @@
+//! Replay a saved game record, checking all moves.
+use blobwar::record::GameRecord;
+use std::env::args;
+
+fn main() {
+    let path = args().nth(1).expect("usage: replay game.json");
+    let record = GameRecord::load(&path).expect("failed loading game record");
+    let board = record.board().expect("invalid start configuration");
+    println!(
+        "{} (red) against {} (blue) on {}",
+        record.players[0],
+        record.players[1],
+        record.board.as_deref().unwrap_or("unknown board")
+    );
+    let replay = record.replay(&board).expect("invalid start configuration");
+    println!(
+        "{}",
+        record.start(&board).expect("invalid start configuration")
+    );
+    for (step, recorded) in replay.zip(&record.moves) {
+        match step {
+            Ok(state) => match recorded.movement {
+                Some(movement) => println!("{} ({} ms){}", movement, recorded.millis, state),
+                None => println!("pass ({} ms){}", recorded.millis, state),
+            },
+            Err(error) => {
+                println!("invalid record: {}", error);
+                return;
+            }
+        }
+    }
+    match record.validate() {
+        Ok(()) => println!("{:?}", record.result),
+        Err(error) => println!("invalid record: {}", error),
+    }
+}
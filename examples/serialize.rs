@@ -6,7 +6,8 @@ fn main() {
     println!("start: {}", start_state);
     let string = start_state.serialize();
     println!("string: {}", string);
-    let deserialized_board = Board::deserialize(&string);
-    let deserialized_configuration = Configuration::deserialize(&string, &deserialized_board);
+    let deserialized_board = Board::deserialize(&string).expect("invalid board");
+    let deserialized_configuration =
+        Configuration::deserialize(&string, &deserialized_board).expect("invalid configuration");
     println!("deserialized: {}", deserialized_configuration);
 }
@@ -0,0 +1,51 @@
+//! Measure how parallel alpha - beta scales with the number of threads.
+//! Usage : smp [depth] [max threads]
+use blobwar::configuration::Configuration;
+use blobwar::evaluation::Material;
+use blobwar::strategy::{ParallelAlphaBeta, SearchContext, Strategy};
+use std::env::args;
+use std::thread::available_parallelism;
+use std::time::Duration;
+
+fn main() {
+    let depth = args().nth(1).map_or(6, |d| d.parse().expect("invalid depth"));
+    let max_threads = args().nth(2).map_or_else(
+        || available_parallelism().map_or(1, |n| n.get()),
+        |t| t.parse().expect("invalid threads number"),
+    );
+    let board = Default::default();
+    let mut positions = Vec::new();
+    let mut state = Configuration::new(&board);
+    for turn in 0..8 {
+        positions.push(state);
+        let movement = state.movements().nth(turn * 7 % 13);
+        match movement {
+            Some(movement) => state.apply_movement(&movement),
+            None => state.apply_skip(),
+        };
+    }
+    let mut reference = None;
+    let mut threads = 1;
+    while threads <= max_threads {
+        let mut time = Duration::default();
+        let mut nodes = 0;
+        for state in &positions {
+            // fresh table for each position : we measure the search, not what was left over
+            let mut strategy = ParallelAlphaBeta::new(depth, Material).threads(threads);
+            let mut context = SearchContext::default();
+            strategy.compute_next_move(state, &mut context);
+            time += context.elapsed();
+            nodes += context.nodes();
+        }
+        let reference = *reference.get_or_insert(time);
+        println!(
+            "{} threads: {:?}, {} nodes ({:.0} nodes/s), speedup {:.2}",
+            threads,
+            time,
+            nodes,
+            nodes as f64 / time.as_secs_f64(),
+            reference.as_secs_f64() / time.as_secs_f64()
+        );
+        threads *= 2;
+    }
+}
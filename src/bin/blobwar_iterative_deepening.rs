@@ -10,14 +10,18 @@ fn main() {
     let strategy_index = env::args()
         .nth(2)
         .expect("missing argument to iterative deepening");
-    let board = Board::deserialize(&config_string);
-    let configuration = Configuration::deserialize(&config_string, &board);
+    let segment = env::args()
+        .nth(3)
+        .expect("missing shared memory segment to iterative deepening");
+    let board = Board::deserialize(&config_string).expect("invalid board");
+    let configuration =
+        Configuration::deserialize(&config_string, &board).expect("invalid configuration");
     match strategy_index
         .parse::<usize>()
         .expect("error parsing strategy integer")
     {
-        0 => min_max_anytime(&configuration),
-        1 => alpha_beta_anytime(&configuration),
+        0 => min_max_anytime(&configuration, &segment),
+        1 => alpha_beta_anytime(&configuration, &segment),
         _ => panic!("invalid strategy number"),
     }
 }
@@ -1,28 +1,139 @@
-use blobwar::board::Board;
-use blobwar::configuration::Configuration;
-use blobwar::strategy::{IterativeDeepening, IterativeStrategy, Strategy};
+use blobwar::board::{Board, DeserializeError};
+use blobwar::configuration::{Configuration, Movement};
+use blobwar::protocol::{Message, VERSION};
+use blobwar::strategy::{spec, SearchContext, SearchLimits, Strategy};
 
 use std::env::args;
 use std::io::Write;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader};
 use std::net::TcpStream;
+use std::time::Duration;
+
+/// Compute our move on given serialized configuration.
+fn answer(
+    strategy: &mut dyn Strategy,
+    configuration: &str,
+    limits: SearchLimits,
+) -> Result<Option<Movement>, DeserializeError> {
+    let board = Board::deserialize(configuration)?;
+    let game = Configuration::deserialize(configuration, &board)?;
+    Ok(strategy.compute_next_move(&game, &mut SearchContext::new(limits)))
+}
+
+/// Play with a legacy server : configurations come as bare lines, moves go back as json.
+fn play_legacy<I: Iterator<Item = String>>(
+    strategy: &mut dyn Strategy,
+    mut sending: TcpStream,
+    lines: I,
+) {
+    for line in lines {
+        // a malformed configuration is reported and answered by a pass instead of crashing
+        let next_move = answer(strategy, &line, Default::default()).unwrap_or_else(|error| {
+            eprintln!("invalid configuration from server ({}): {:?}", error, line);
+            None
+        });
+        // legacy servers number cells in an 8 x 8 grid
+        let next_move = next_move.and_then(Movement::to_legacy);
+        serde_json::to_writer(&mut sending, &next_move).expect("sending back movement failed");
+        sending.write_all(b"\n").expect("newline failed");
+    }
+}
+
+/// Play with a server speaking the message protocol, once its hello has been received.
+fn play<R: BufRead>(strategy: &mut dyn Strategy, sending: TcpStream, mut receiving: R) {
+    Message::Hello {
+        version: VERSION,
+        name: strategy.to_string(),
+    }
+    .send(&sending)
+    .expect("sending hello failed");
+    loop {
+        let message = match Message::receive(&mut receiving) {
+            Ok(message) => message,
+            Err(ref error) if error.kind() == io::ErrorKind::InvalidData => {
+                eprintln!("invalid message from server: {}", error);
+                continue;
+            }
+            Err(error) => {
+                eprintln!("connection to server lost: {}", error);
+                return;
+            }
+        };
+        match message {
+            Message::Colour { player } => {
+                println!("we play {}", if player { "blue" } else { "red" })
+            }
+            Message::Position {
+                configuration,
+                millis,
+            } => {
+                // keep a margin for the network
+                let limits = millis.map_or_else(SearchLimits::default, |millis| {
+                    SearchLimits::default().time(Duration::from_millis(millis * 9 / 10))
+                });
+                let next_move = answer(strategy, &configuration, limits).unwrap_or_else(|error| {
+                    eprintln!(
+                        "invalid configuration from server ({}): {:?}",
+                        error, configuration
+                    );
+                    None
+                });
+                Message::play(next_move)
+                    .send(&sending)
+                    .expect("sending back movement failed");
+            }
+            Message::IllegalMove { reason } => eprintln!("server rejected our move: {}", reason),
+            Message::Result { red, blue, forfeit } => {
+                println!("game over : {} red, {} blue", red, blue);
+                if let Some(forfeit) = forfeit {
+                    let player = if forfeit.player { "blue" } else { "red" };
+                    println!("{} loses by forfeit ({})", player, forfeit.reason)
+                }
+            }
+            Message::Bye => return,
+            message => eprintln!("unexpected message from server: {:?}", message),
+        }
+    }
+}
 
 fn main() {
+    // usage: client address [port [strategy]]
     let address = args().nth(1).expect("missing machine name or IP address");
-    let mut strategy = IterativeDeepening::new(IterativeStrategy::AlphaBeta);
-    let mut sending =
-        TcpStream::connect((address.as_str(), 12_345)).expect("failed connecting to server");
+    let port = args()
+        .nth(2)
+        .map_or(12_345, |port| port.parse().expect("invalid port"));
+    let mut strategy = spec::parse(&args().nth(3).unwrap_or_else(|| "iterative".to_owned()))
+        .expect("invalid strategy");
+    let sending =
+        TcpStream::connect((address.as_str(), port)).expect("failed connecting to server");
     sending.set_nodelay(true).expect("failed setting no delay");
-    let receiving = BufReader::new(sending.try_clone().expect("failed cloning socket"));
+    let mut receiving = BufReader::new(sending.try_clone().expect("failed cloning socket"));
 
-    for line in receiving
-        .lines()
-        .map(|r| r.expect("failed reading configuration from server"))
+    // servers speaking the message protocol start with hello, legacy ones with a configuration
+    let mut first = String::new();
+    if receiving
+        .read_line(&mut first)
+        .expect("failed reading from server")
+        == 0
     {
-        let board = Board::deserialize(&line);
-        let game = Configuration::deserialize(&line, &board);
-        let next_move = strategy.compute_next_move(&game, None);
-        serde_json::to_writer(&mut sending, &next_move).expect("sending back movement failed");
-        sending.write(b"\n").expect("newline failed");
+        return;
+    }
+    match serde_json::from_str(&first) {
+        Ok(Message::Hello { version, name }) => {
+            if version != VERSION {
+                eprintln!(
+                    "{} speaks protocol version {} (we speak {})",
+                    name, version, VERSION
+                );
+            }
+            play(&mut *strategy, sending, receiving)
+        }
+        _ => {
+            let lines = receiving
+                .lines()
+                .map(|r| r.expect("failed reading configuration from server"));
+            let first = first.trim_end_matches(['\r', '\n']).to_owned();
+            play_legacy(&mut *strategy, sending, std::iter::once(first).chain(lines))
+        }
     }
 }
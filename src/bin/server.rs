@@ -1,23 +1,197 @@
+//! Host games between local engines and remote clients.
 use blobwar::board::Board;
+use blobwar::clock::TimeControl;
 use blobwar::configuration::Configuration;
-use blobwar::strategy::{IterativeDeepening, IterativeStrategy, NetworkPlayer};
+use blobwar::strategy::{spec, NetworkPlayer, Strategy};
 
 use std::env::args;
 use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::time::Duration;
+
+const USAGE: &str = "usage: server [options] [board [record.json]]
+options :
+  --bind ADDRESS  address to listen on (0.0.0.0)
+  --port PORT     port to listen on (12345)
+  --board BOARD   board name or file (standard)
+  --time MS       time allowed for each move in milliseconds (unlimited)
+  --game-time MS  time allowed for all moves of a game in milliseconds (unlimited)
+  --games N       number of games to play (1)
+  --red SEAT      red player (iterative)
+  --blue SEAT     blue player (network)
+  --record PATH   save game records (numbered if several games are played)
+  --legacy        network players speak the legacy protocol (8x8 boards only)
+seats are 'network' for a remote client or a strategy like 'alphabeta:4' or 'mcts:500'";
+
+/// Command line options.
+struct Options {
+    bind: String,
+    port: u16,
+    board: String,
+    time_control: TimeControl,
+    games: u32,
+    seats: [String; 2],
+    record: Option<String>,
+    legacy: bool,
+}
+
+impl Options {
+    fn parse() -> Result<Self, String> {
+        let mut options = Options {
+            bind: "0.0.0.0".to_owned(),
+            port: 12_345,
+            board: "standard".to_owned(),
+            time_control: TimeControl::default(),
+            games: 1,
+            seats: ["iterative".to_owned(), "network".to_owned()],
+            record: None,
+            legacy: false,
+        };
+        let mut arguments = args().skip(1);
+        let mut positionals = 0;
+        while let Some(argument) = arguments.next() {
+            let mut value = || {
+                arguments
+                    .next()
+                    .ok_or_else(|| format!("missing value for {}", argument))
+            };
+            let invalid = |value: String| format!("invalid value '{}'", value);
+            match argument.as_str() {
+                "--bind" => options.bind = value()?,
+                "--port" => {
+                    let port = value()?;
+                    options.port = port.parse().map_err(|_| invalid(port))?
+                }
+                "--board" => options.board = value()?,
+                "--time" | "--game-time" => {
+                    let time = value()?;
+                    let time = Duration::from_millis(time.parse().map_err(|_| invalid(time))?);
+                    options.time_control = if argument == "--time" {
+                        options.time_control.per_move(time)
+                    } else {
+                        options.time_control.per_game(time)
+                    }
+                }
+                "--games" => {
+                    let games = value()?;
+                    options.games = games.parse().map_err(|_| invalid(games))?
+                }
+                "--red" => options.seats[0] = value()?,
+                "--blue" => options.seats[1] = value()?,
+                "--record" => options.record = Some(value()?),
+                "--legacy" => options.legacy = true,
+                "--help" | "-h" => return Err(String::new()),
+                _ if argument.starts_with("--") => {
+                    return Err(format!("unknown option {}", argument))
+                }
+                _ => {
+                    // board and record path can also be given directly
+                    match positionals {
+                        0 => options.board = argument,
+                        1 => options.record = Some(argument),
+                        _ => return Err(format!("unexpected argument {}", argument)),
+                    }
+                    positionals += 1;
+                }
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Path of the record of given game : numbered when playing several games.
+fn record_path(path: &str, game: u32, games: u32) -> PathBuf {
+    let path = Path::new(path);
+    if games == 1 {
+        return path.to_owned();
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    match path.extension() {
+        Some(extension) => {
+            path.with_file_name(format!("{}-{}.{}", stem, game, extension.to_string_lossy()))
+        }
+        None => path.with_file_name(format!("{}-{}", stem, game)),
+    }
+}
+
+/// Wait for a client to take a seat. Clients failing to connect are skipped.
+fn accept(listener: &TcpListener, legacy: bool) -> NetworkPlayer {
+    loop {
+        let (connection, address) = match listener.accept() {
+            Ok(client) => client,
+            Err(error) => {
+                eprintln!("failed accepting client: {}", error);
+                continue;
+            }
+        };
+        let player = if legacy {
+            NetworkPlayer::legacy(connection)
+        } else {
+            NetworkPlayer::new(connection)
+        };
+        match player {
+            Ok(player) => return player,
+            Err(error) => eprintln!("failed greeting client {}: {}", address, error),
+        }
+    }
+}
 
 fn main() {
-    let map_name = args().nth(1).unwrap_or("standard".to_owned());
-    // we need two players
-    let listener = TcpListener::bind("0.0.0.0:12345").expect("bind failed");
-    let mut players = listener
-        .incoming()
-        .map(|c| NetworkPlayer::new(c.expect("accept failed")));
-
-    // create board and start game
-    let board = Board::load(map_name).expect("failed loading map");
-    let mut game = Configuration::new(&board);
-    game.battle(
-        IterativeDeepening::new(IterativeStrategy::AlphaBeta),
-        players.next().unwrap(),
-    );
+    let options = Options::parse().unwrap_or_else(|error| {
+        eprintln!("{}\n{}", error, USAGE);
+        exit(1)
+    });
+    let board = Board::load(&options.board).expect("failed loading map");
+    if options.legacy && !board.is_legacy() {
+        eprintln!(
+            "legacy clients only play on 8x8 boards ({} is {}x{})",
+            options.board, board.width, board.height
+        );
+        exit(1)
+    }
+
+    // take seats : remote players connect in order (red first)
+    let listener = if options.seats.iter().any(|seat| seat == "network") {
+        Some(TcpListener::bind((options.bind.as_str(), options.port)).expect("bind failed"))
+    } else {
+        None
+    };
+    let mut seats = Vec::new();
+    for (seat, colour) in options.seats.iter().zip(&["red", "blue"]) {
+        let player: Box<dyn Strategy> = match (seat.as_str(), &listener) {
+            ("network", Some(listener)) => {
+                println!("waiting for {} player on port {}", colour, options.port);
+                Box::new(accept(listener, options.legacy))
+            }
+            _ => spec::parse(seat).unwrap_or_else(|error| {
+                eprintln!("invalid {} seat: {}\n{}", colour, error, USAGE);
+                exit(1)
+            }),
+        };
+        seats.push(player);
+    }
+
+    // red wins, blue wins, draws
+    let mut results = [0; 3];
+    for game in 1..=options.games {
+        let mut state = Configuration::new(&board);
+        let (red, blue) = seats.split_at_mut(1);
+        let mut record = state.battle_with_clock(options.time_control, &mut red[0], &mut blue[0]);
+        let winner = record.result.and_then(|result| result.winner());
+        results[winner.map_or(2, |winner| winner as usize)] += 1;
+        // save game record if asked to
+        if let Some(path) = &options.record {
+            record.board = Some(options.board.clone());
+            record
+                .save(record_path(path, game, options.games))
+                .expect("failed saving game record");
+        }
+    }
+    if options.games > 1 {
+        println!(
+            "{} : {} wins, {} : {} wins, {} draws",
+            seats[0], results[0], seats[1], results[1], results[2]
+        );
+    }
 }
@@ -0,0 +1,389 @@
+//! Play matches between engines and display a crosstable.
+use blobwar::board::Board;
+use blobwar::clock::TimeControl;
+use blobwar::configuration::Configuration;
+use blobwar::record::GameResult;
+use blobwar::strategy::spec;
+
+use std::env::args;
+use std::process::exit;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::channel;
+use std::thread::{available_parallelism, scope};
+use std::time::Duration;
+
+const USAGE: &str = "usage: tournament [options] strategy strategy...
+options :
+  --board BOARD    board name or file, can be repeated (standard)
+  --gauntlet       first strategy plays all others (default is round-robin)
+  --rounds N       games of each pairing with each colour on each board (1)
+  --threads N      games played at once (number of cores, or 1 with parallel strategies)
+  --time MS        time allowed for each move in milliseconds (unlimited)
+  --game-time MS   time allowed for all moves of a game in milliseconds (unlimited)
+  --max-plies N    games are decided on blob counts after that many plies, for boards
+                   without their own limit (400)
+strategies are given like 'alphabeta:4' or 'mcts:500', see blobwar::strategy::spec";
+
+/// Command line options.
+struct Options {
+    boards: Vec<String>,
+    gauntlet: bool,
+    rounds: u32,
+    /// Games played at once, if given.
+    threads: Option<usize>,
+    time_control: TimeControl,
+    max_plies: u32,
+    strategies: Vec<String>,
+}
+
+impl Options {
+    fn parse() -> Result<Self, String> {
+        let mut options = Options {
+            boards: Vec::new(),
+            gauntlet: false,
+            rounds: 1,
+            threads: None,
+            time_control: TimeControl::default(),
+            max_plies: 400,
+            strategies: Vec::new(),
+        };
+        let mut arguments = args().skip(1);
+        while let Some(argument) = arguments.next() {
+            let mut value = || {
+                arguments
+                    .next()
+                    .ok_or_else(|| format!("missing value for {}", argument))
+            };
+            fn number<T: std::str::FromStr>(value: String) -> Result<T, String> {
+                value
+                    .parse()
+                    .map_err(|_| format!("invalid value '{}'", value))
+            }
+            match argument.as_str() {
+                "--board" => options.boards.push(value()?),
+                "--gauntlet" => options.gauntlet = true,
+                "--rounds" => options.rounds = number(value()?)?,
+                "--threads" => options.threads = Some(number(value()?)?),
+                "--time" => {
+                    let time = Duration::from_millis(number(value()?)?);
+                    options.time_control = options.time_control.per_move(time)
+                }
+                "--game-time" => {
+                    let time = Duration::from_millis(number(value()?)?);
+                    options.time_control = options.time_control.per_game(time)
+                }
+                "--max-plies" => options.max_plies = number(value()?)?,
+                "--help" | "-h" => return Err(String::new()),
+                _ if argument.starts_with("--") => {
+                    return Err(format!("unknown option {}", argument))
+                }
+                _ => options.strategies.push(argument),
+            }
+        }
+        if options.boards.is_empty() {
+            options.boards.push("standard".to_owned());
+        }
+        if options.strategies.len() < 2 {
+            return Err("at least two strategies are needed".to_owned());
+        }
+        if options.threads == Some(0) {
+            return Err("at least one thread is needed".to_owned());
+        }
+        Ok(options)
+    }
+
+    /// Number of games to play at once : one per core by default, but parallel strategies
+    /// already use all cores on their own.
+    fn threads(&self) -> usize {
+        self.threads.unwrap_or_else(|| {
+            let parallel = self
+                .strategies
+                .iter()
+                .any(|strategy| strategy.split(':').any(|part| part == "parallel"));
+            if parallel {
+                1
+            } else {
+                available_parallelism().map_or(1, |threads| threads.get())
+            }
+        })
+    }
+}
+
+/// Pairs of strategies (indices) meeting each other : all of them or, for a gauntlet, the
+/// first one against all others.
+fn pairings(strategies: usize, gauntlet: bool) -> Vec<(usize, usize)> {
+    if gauntlet {
+        (1..strategies).map(|opponent| (0, opponent)).collect()
+    } else {
+        (0..strategies)
+            .flat_map(|first| (first + 1..strategies).map(move |second| (first, second)))
+            .collect()
+    }
+}
+
+/// A game to play : board index, red and blue strategy indices.
+#[derive(Copy, Clone)]
+struct Game {
+    board: usize,
+    red: usize,
+    blue: usize,
+}
+
+/// Results of one strategy against one opponent.
+#[derive(Copy, Clone, Default)]
+struct Score {
+    wins: u32,
+    draws: u32,
+    losses: u32,
+    /// Sum of own blobs minus opponent blobs over all games.
+    difference: i32,
+}
+
+impl Score {
+    fn add(&mut self, other: &Score) {
+        self.wins += other.wins;
+        self.draws += other.draws;
+        self.losses += other.losses;
+        self.difference += other.difference;
+    }
+
+    fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Points scored, a draw being worth half a win.
+    fn points(&self) -> f64 {
+        f64::from(self.wins) + f64::from(self.draws) / 2.0
+    }
+
+    /// Average blob difference.
+    fn average(&self) -> f64 {
+        f64::from(self.difference) / f64::from(self.games().max(1))
+    }
+}
+
+/// Score of each strategy against each other.
+struct Crosstable(Vec<Vec<Score>>);
+
+impl Crosstable {
+    fn new(strategies: usize) -> Self {
+        Crosstable(vec![vec![Score::default(); strategies]; strategies])
+    }
+
+    fn record(&mut self, game: Game, result: &GameResult) {
+        let winner = result.winner();
+        let seats = [(false, game.red, game.blue), (true, game.blue, game.red)];
+        for &(player, own, opponent) in &seats {
+            let score = &mut self.0[own][opponent];
+            match winner {
+                Some(winner) if winner == player => score.wins += 1,
+                Some(_) => score.losses += 1,
+                None => score.draws += 1,
+            }
+            let difference = i32::from(result.red) - i32::from(result.blue);
+            score.difference += if player { -difference } else { difference };
+        }
+    }
+
+    fn total(&self, strategy: usize) -> Score {
+        let mut total = Score::default();
+        for score in &self.0[strategy] {
+            total.add(score);
+        }
+        total
+    }
+
+    /// Display the table, best strategies first.
+    fn display(&self, names: &[String]) {
+        let mut ranking: Vec<usize> = (0..names.len()).collect();
+        ranking.sort_by(|&a, &b| {
+            self.total(b)
+                .points()
+                .partial_cmp(&self.total(a).points())
+                .unwrap()
+        });
+        let width = names.iter().map(|name| name.len()).max().unwrap_or(0);
+        print!("{:width$}  ", "", width = width + 3);
+        for rank in 1..=names.len() {
+            print!("{:>9}", rank);
+        }
+        println!("{:>12}{:>8}{:>8}", "total", "points", "blobs");
+        for (rank, &strategy) in ranking.iter().enumerate() {
+            print!(
+                "{:>2} {:width$}  ",
+                rank + 1,
+                names[strategy],
+                width = width
+            );
+            for &opponent in &ranking {
+                let score = &self.0[strategy][opponent];
+                if score.games() == 0 {
+                    print!("{:>9}", ".");
+                } else {
+                    print!(
+                        "{:>9}",
+                        format!("{}-{}-{}", score.wins, score.draws, score.losses)
+                    );
+                }
+            }
+            let total = self.total(strategy);
+            println!(
+                "{:>12}{:>8.1}{:>+8.1}",
+                format!("{}-{}-{}", total.wins, total.draws, total.losses),
+                total.points(),
+                total.average()
+            );
+        }
+        println!("scores are wins-draws-losses, blobs is the average blob difference");
+    }
+}
+
+fn main() {
+    let options = Options::parse().unwrap_or_else(|error| {
+        eprintln!("{}\n{}", error, USAGE);
+        exit(1)
+    });
+    // check specifications now : each game builds its own strategies in its thread
+    for strategy in &options.strategies {
+        if strategy.split(':').any(|part| part == "human") {
+            eprintln!("human players cannot take part in tournaments");
+            exit(1)
+        }
+        if let Err(error) = spec::parse(strategy) {
+            eprintln!("invalid strategy {}: {}\n{}", strategy, error, USAGE);
+            exit(1)
+        }
+    }
+    let boards: Vec<Board> = options
+        .boards
+        .iter()
+        .map(|name| {
+            let mut board = Board::load(name).unwrap_or_else(|error| {
+                eprintln!("failed loading board {}: {}", name, error);
+                exit(1)
+            });
+            // jumps allow endless games
+            board.rules.max_plies = board.rules.max_plies.or(Some(options.max_plies));
+            board
+        })
+        .collect();
+
+    let mut games = Vec::new();
+    for (first, second) in pairings(options.strategies.len(), options.gauntlet) {
+        for board in 0..boards.len() {
+            for _ in 0..options.rounds {
+                games.push(Game {
+                    board,
+                    red: first,
+                    blue: second,
+                });
+                games.push(Game {
+                    board,
+                    red: second,
+                    blue: first,
+                });
+            }
+        }
+    }
+
+    let mut crosstable = Crosstable::new(options.strategies.len());
+    let next_game = AtomicUsize::new(0);
+    let (finished, results) = channel();
+    scope(|scope| {
+        for _ in 0..options.threads().min(games.len()) {
+            let finished = finished.clone();
+            let (games, boards, next_game) = (&games, &boards, &next_game);
+            let (strategies, time_control) = (&options.strategies, options.time_control);
+            scope.spawn(move || {
+                while let Some(&game) = games.get(next_game.fetch_add(1, Ordering::Relaxed)) {
+                    let red = spec::parse(&strategies[game.red]).unwrap();
+                    let blue = spec::parse(&strategies[game.blue]).unwrap();
+                    let mut state = Configuration::new(&boards[game.board]);
+                    let record = state.quiet_battle(time_control, red, blue);
+                    finished.send((game, record.result.unwrap())).unwrap();
+                }
+            });
+        }
+        drop(finished);
+        for (played, (game, result)) in results.iter().enumerate() {
+            let mut line = format!(
+                "game {}/{} on {} : {} (red) {} - {} {} (blue)",
+                played + 1,
+                games.len(),
+                options.boards[game.board],
+                options.strategies[game.red],
+                result.red,
+                result.blue,
+                options.strategies[game.blue],
+            );
+            if let Some(forfeit) = result.forfeit {
+                let player = if forfeit.player { "blue" } else { "red" };
+                line += &format!(", {} forfeits ({})", player, forfeit.reason);
+            }
+            println!("{}", line);
+            crosstable.record(game, &result);
+        }
+    });
+    println!();
+    crosstable.display(&options.strategies);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blobwar::record::{Forfeit, ForfeitReason};
+
+    #[test]
+    fn pairings_cover_all_opponents() {
+        assert_eq!(pairings(3, false), [(0, 1), (0, 2), (1, 2)]);
+        assert_eq!(pairings(3, true), [(0, 1), (0, 2)]);
+        assert_eq!(pairings(4, false).len(), 6);
+        assert!(pairings(1, false).is_empty());
+    }
+
+    #[test]
+    fn crosstable_scores_both_colours() {
+        let game = |red, blue| Game {
+            board: 0,
+            red,
+            blue,
+        };
+        let result = |red, blue| GameResult {
+            red,
+            blue,
+            forfeit: None,
+        };
+        let mut crosstable = Crosstable::new(3);
+        crosstable.record(game(0, 1), &result(5, 2));
+        // 0 playing blue
+        crosstable.record(game(1, 0), &result(1, 4));
+        // 0 loses by forfeit although ahead
+        let forfeit = Some(Forfeit {
+            player: false,
+            reason: ForfeitReason::Timeout,
+        });
+        crosstable.record(
+            game(0, 1),
+            &GameResult {
+                forfeit,
+                ..result(6, 1)
+            },
+        );
+        crosstable.record(game(1, 2), &result(3, 3));
+
+        let score = crosstable.0[0][1];
+        assert_eq!((score.wins, score.draws, score.losses), (2, 0, 1));
+        assert_eq!(score.difference, 3 + 3 + 5);
+        let score = crosstable.0[1][0];
+        assert_eq!((score.wins, score.draws, score.losses), (1, 0, 2));
+        assert_eq!(score.difference, -11);
+        assert_eq!(crosstable.0[0][2].games(), 0);
+
+        let total = crosstable.total(1);
+        assert_eq!((total.wins, total.draws, total.losses), (1, 1, 2));
+        assert_eq!(total.points(), 1.5);
+        assert_eq!(total.average(), -11.0 / 4.0);
+        let total = crosstable.total(2);
+        assert_eq!((total.games(), total.difference), (1, 0));
+    }
+}
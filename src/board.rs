@@ -1,15 +1,294 @@
 //! Board related features. Provides the `Board` structure storing holes.
+//!
+//! Board files describe the board row by row : 'x' marks holes, 'r' and 'b' red and blue
+//! starting blobs and 'O' empty cells. If no starting blobs are
+//! given, red starts in the top left and bottom right corners and blue in the two others.
+//! Rows can be preceded by "key: value" header lines :
+//! - `name` : name of the board to display
+//! - `max_plies` : games end (and are decided on blob counts) after that many plies
+//!
+//! Empty lines and lines starting with '#' are ignored. Empty cells can also be written '.' ;
+//! all rows must have the same length and all playable cells must be reachable from a
+//! starting blob.
 use std::cmp::{max, min};
+use std::error::Error;
+use std::fmt;
 use std::fs::File;
 use std::io;
 use std::io::prelude::*;
 use std::path::Path;
 
-use super::positions::{BoardPosition, Position, Positions};
+use super::positions::{BoardPosition, Position, Positions, CELLS, MAX_SIDE};
+
+/// Boards available by name without any file, as (name, content of the board file).
+pub const BUILT_IN: [(&str, &str); 17] = [
+    ("chess", include_str!("../boards/chess")),
+    ("constrained", include_str!("../boards/constrained")),
+    ("cross", include_str!("../boards/cross")),
+    ("duel", include_str!("../boards/duel")),
+    ("fortress", include_str!("../boards/fortress")),
+    ("inside", include_str!("../boards/inside")),
+    ("irregular", include_str!("../boards/irregular")),
+    ("island", include_str!("../boards/island")),
+    ("path", include_str!("../boards/path")),
+    ("quantum", include_str!("../boards/quantum")),
+    ("rings", include_str!("../boards/rings")),
+    ("standard", include_str!("../boards/standard")),
+    ("standard10", include_str!("../boards/standard10")),
+    ("standard12", include_str!("../boards/standard12")),
+    ("strange", include_str!("../boards/strange")),
+    ("test", include_str!("../boards/test")),
+    ("x", include_str!("../boards/x")),
+];
+
+#[derive(Debug)]
+/// Why a board could not be loaded. Lines and columns start at 1.
+pub enum BoardError {
+    /// Reading the board file failed.
+    Io(io::Error),
+    /// There is no row.
+    Empty,
+    /// Row at given line does not have the same length as the first one.
+    RowLength {
+        /// Line of the row.
+        line: usize,
+        /// Length of the first row.
+        expected: usize,
+        /// Length of this row.
+        found: usize,
+    },
+    /// Cell at given line and column is beyond `MAX_SIDE` rows or columns.
+    TooLarge {
+        /// Line of the cell.
+        line: usize,
+        /// Column of the cell.
+        column: usize,
+    },
+    /// Cell at given line and column is not one of 'x', 'O', '.', 'r' or 'b'.
+    UnknownCharacter {
+        /// Line of the cell.
+        line: usize,
+        /// Column of the cell.
+        column: usize,
+        /// Character found.
+        character: char,
+    },
+    /// Header at given line is unknown, has an invalid value or comes after rows.
+    Header {
+        /// Line of the header.
+        line: usize,
+        /// Key of the header.
+        header: String,
+    },
+    /// Only one player has starting blobs (the one missing them is given).
+    MissingStart(bool),
+    /// A starting cell (corner by default) at given line and column is a hole.
+    StartOnHole {
+        /// Line of the cell.
+        line: usize,
+        /// Column of the cell.
+        column: usize,
+    },
+    /// Red and blue both start on the cell at given line and column (default corners of a board
+    /// one cell wide or high).
+    SharedStart {
+        /// Line of the cell.
+        line: usize,
+        /// Column of the cell.
+        column: usize,
+    },
+    /// Playable cell at given line and column cannot be reached from any starting blob.
+    Disconnected {
+        /// Line of the cell.
+        line: usize,
+        /// Column of the cell.
+        column: usize,
+    },
+}
+
+impl fmt::Display for BoardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BoardError::Io(error) => write!(f, "failed reading board: {}", error),
+            BoardError::Empty => write!(f, "board has no rows"),
+            BoardError::RowLength {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {}: row has {} cells instead of {}",
+                line, found, expected
+            ),
+            BoardError::TooLarge { line, column } => write!(
+                f,
+                "line {}, column {}: boards have at most {} rows and columns",
+                line, column, MAX_SIDE
+            ),
+            BoardError::UnknownCharacter {
+                line,
+                column,
+                character,
+            } => write!(
+                f,
+                "line {}, column {}: unknown cell '{}' (expected x, O, ., r or b)",
+                line, column, character
+            ),
+            BoardError::Header { line, header } => {
+                write!(f, "line {}: invalid or misplaced header '{}'", line, header)
+            }
+            BoardError::MissingStart(player) => write!(
+                f,
+                "{} has no starting blobs while the other player has",
+                ["red", "blue"][*player as usize]
+            ),
+            BoardError::StartOnHole { line, column } => write!(
+                f,
+                "line {}, column {}: starting cell is a hole (declare starting blobs with r and b)",
+                line, column
+            ),
+            BoardError::SharedStart { line, column } => write!(
+                f,
+                "line {}, column {}: red and blue both start on this cell (declare starting \
+                 blobs with r and b)",
+                line, column
+            ),
+            BoardError::Disconnected { line, column } => write!(
+                f,
+                "line {}, column {}: cell cannot be reached from any starting blob",
+                line, column
+            ),
+        }
+    }
+}
+
+impl Error for BoardError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BoardError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for BoardError {
+    fn from(error: io::Error) -> Self {
+        BoardError::Io(error)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// Why a serialized `Configuration` (or its `Board`) cannot be deserialized.
+/// Indices count characters from the start of the string (the player code is at index 0).
+pub enum DeserializeError {
+    /// The string is empty.
+    MissingPlayer,
+    /// The string does not start with a player code ('0' or '1').
+    InvalidPlayer(char),
+    /// Legacy format (without '/') with a number of cells other than 64.
+    WrongLength(usize),
+    /// Given row (from 0) does not have as many cells as the first one.
+    RowLength {
+        /// Index of the row.
+        row: usize,
+        /// Cells of the first row.
+        expected: usize,
+        /// Cells of this row.
+        found: usize,
+    },
+    /// No cells, or more than `MAX_SIDE` rows or columns.
+    WrongSize {
+        /// Number of columns.
+        width: usize,
+        /// Number of rows.
+        height: usize,
+    },
+    /// Cell which is not 'r', 'b', 'h' or ' '.
+    InvalidCell {
+        /// Index of the character.
+        index: usize,
+        /// The character itself.
+        character: char,
+    },
+    /// A blob stands on a hole of the board.
+    BlobOnHole(usize),
+    /// The configuration and the board have different sizes.
+    SizeMismatch {
+        /// Size of the serialized configuration.
+        found: (u8, u8),
+        /// Size of the board.
+        expected: (u8, u8),
+    },
+}
+
+impl fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DeserializeError::MissingPlayer => write!(f, "empty configuration"),
+            DeserializeError::InvalidPlayer(code) => {
+                write!(f, "invalid player code '{}' (expected 0 or 1)", code)
+            }
+            DeserializeError::WrongLength(found) => {
+                write!(f, "configuration has {} cells instead of 64", found)
+            }
+            DeserializeError::RowLength {
+                row,
+                expected,
+                found,
+            } => write!(f, "row {} has {} cells instead of {}", row, found, expected),
+            DeserializeError::WrongSize { width, height } => write!(
+                f,
+                "invalid size {}x{} (boards have 1 to {} rows and columns)",
+                width, height, MAX_SIDE
+            ),
+            DeserializeError::InvalidCell { index, character } => write!(
+                f,
+                "index {}: invalid cell '{}' (expected r, b, h or space)",
+                index, character
+            ),
+            DeserializeError::BlobOnHole(index) => {
+                write!(f, "index {}: blob on a hole of the board", index)
+            }
+            DeserializeError::SizeMismatch { found, expected } => write!(
+                f,
+                "configuration is {}x{} but the board is {}x{}",
+                found.0, found.1, expected.0, expected.1
+            ),
+        }
+    }
+}
+
+impl Error for DeserializeError {}
+
+/// Checked content of a serialized `Configuration`.
+pub(crate) struct Serialized {
+    pub(crate) current_player: bool,
+    pub(crate) width: u8,
+    pub(crate) height: u8,
+    /// Position, index in the string and code of each cell ('r', 'b', 'h' or ' ').
+    pub(crate) cells: Vec<(Position, usize, char)>,
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+/// Optional rule parameters of a board.
+pub struct Rules {
+    /// Games end after that many plies (passes included), if given.
+    pub max_plies: Option<u32>,
+}
 
 /// Board representation.
 pub struct Board {
-    /// `Positions` of the holes.
+    /// Name to display, if any.
+    pub name: Option<String>,
+    /// `Positions` of red and blue blobs at the start of games.
+    pub start: [Positions; 2],
+    /// Optional rules.
+    pub rules: Rules,
+    /// Number of columns.
+    pub width: u8,
+    /// Number of rows.
+    pub height: u8,
+    /// `Positions` of the holes (cells outside of the board included).
     pub holes: Positions,
     /// Associate to each position an uncompressed set of neighbouring positions (at distance 1 and at distance 2)
     /// (prefiltered with holes).
@@ -27,10 +306,36 @@ impl Default for Board {
 }
 
 impl Board {
-    /// Compute new `Board` structure from given holes.
+    /// Compute new 8x8 `Board` structure from given holes.
     pub fn new(holes: Positions) -> Self {
+        Board::with_size(8, 8, holes)
+    }
+
+    /// Compute new `Board` structure of given size from given holes.
+    /// Panics if the board does not fit in `MAX_SIDE` x `MAX_SIDE` cells.
+    pub fn with_size(width: u8, height: u8, holes: Positions) -> Self {
+        assert!(
+            (1..=MAX_SIDE).contains(&width) && (1..=MAX_SIDE).contains(&height),
+            "invalid board size {}x{}",
+            width,
+            height
+        );
+        let inside = Positions::from_cells(
+            &(0..height)
+                .flat_map(|y| (0..width).map(move |x| Position::from_2d(x, y)))
+                .collect::<Vec<_>>(),
+        );
+        let (right, bottom) = (width - 1, height - 1);
         let mut board = Board {
-            holes,
+            name: None,
+            start: [
+                Positions::from_cells(&[Position::from_2d(0, 0), Position::from_2d(right, bottom)]),
+                Positions::from_cells(&[Position::from_2d(right, 0), Position::from_2d(0, bottom)]),
+            ],
+            rules: Rules::default(),
+            width,
+            height,
+            holes: holes.union_with(inside.invert()),
             individual_neighbours: [Vec::new(), Vec::new()],
             neighbours: Vec::new(),
         };
@@ -39,39 +344,174 @@ impl Board {
         board
     }
 
-    /// Load a `Board` from given file.
-    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
-        let file = File::open(Path::new("boards").join(path))?;
-        let mut bit = 1u64;
-        let mut positions_code = 0;
-        for line in io::BufReader::new(file).lines() {
-            for character in line?.chars() {
-                if character == 'x' {
-                    positions_code |= bit;
+    /// Load a `Board` from given file or, if there is no such file, the built-in board of that
+    /// name (see `BUILT_IN`).
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, BoardError> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            let built_in = path.to_str().and_then(|name| {
+                BUILT_IN
+                    .iter()
+                    .find(|&&(built_in, _)| built_in == name)
+                    .map(|&(_, text)| text)
+            });
+            if let Some(text) = built_in {
+                return Board::parse(text);
+            }
+        }
+        let mut text = String::new();
+        File::open(path)?.read_to_string(&mut text)?;
+        Board::parse(&text)
+    }
+
+    /// Parse and validate a `Board` from the content of a board file.
+    pub fn parse(text: &str) -> Result<Self, BoardError> {
+        let mut name = None;
+        let mut rules = Rules::default();
+        // rows with their line numbers
+        let mut rows: Vec<(usize, &str)> = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let (number, line) = (index + 1, line.trim_end());
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = match line.split_once(':') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => {
+                    rows.push((number, line));
+                    continue;
+                }
+            };
+            let invalid_header = || BoardError::Header {
+                line: number,
+                header: key.to_owned(),
+            };
+            if !rows.is_empty() {
+                return Err(invalid_header());
+            }
+            match key {
+                "name" => name = Some(value.to_owned()),
+                "max_plies" => rules.max_plies = Some(value.parse().map_err(|_| invalid_header())?),
+                _ => return Err(invalid_header()),
+            }
+        }
+        let width = match rows.first() {
+            Some((_, row)) => row.chars().count(),
+            None => return Err(BoardError::Empty),
+        };
+        let mut holes = Positions::default();
+        let mut start = [Positions::default(); 2];
+        for (y, &(line, row)) in rows.iter().enumerate() {
+            let found = row.chars().count();
+            if found != width {
+                return Err(BoardError::RowLength {
+                    line,
+                    expected: width,
+                    found,
+                });
+            }
+            for (x, character) in row.chars().enumerate() {
+                if x >= MAX_SIDE as usize || y >= MAX_SIDE as usize {
+                    return Err(BoardError::TooLarge {
+                        line,
+                        column: x + 1,
+                    });
+                }
+                let position = Positions::single(Position::from_2d(x as u8, y as u8));
+                match character {
+                    'x' => holes.add(position),
+                    'r' => start[0].add(position),
+                    'b' => start[1].add(position),
+                    'O' | '.' => {}
+                    _ => {
+                        return Err(BoardError::UnknownCharacter {
+                            line,
+                            column: x + 1,
+                            character,
+                        })
+                    }
                 }
-                bit <<= 1;
             }
         }
-        Ok(Board::new(Positions(positions_code)))
+        let mut board = Board::with_size(width as u8, rows.len() as u8, holes);
+        // where cells are in the file
+        let location = |position: Position| {
+            let (x, y) = position.to_2d();
+            (rows[y as usize].0, x as usize + 1)
+        };
+        match (start[0].is_empty(), start[1].is_empty()) {
+            (true, true) => {}
+            (false, false) => board.start = start,
+            (red_missing, _) => return Err(BoardError::MissingStart(!red_missing)),
+        }
+        let shared = board.start[0].intersection_with(board.start[1]);
+        if let Some(position) = shared.positions().next() {
+            let (line, column) = location(position);
+            return Err(BoardError::SharedStart { line, column });
+        }
+        let all_start = board.start[0].union_with(board.start[1]);
+        if let Some(position) = all_start.intersection_with(board.holes).positions().next() {
+            let (line, column) = location(position);
+            return Err(BoardError::StartOnHole { line, column });
+        }
+        if let Some(position) = board.unreachable_cells().positions().next() {
+            let (line, column) = location(position);
+            return Err(BoardError::Disconnected { line, column });
+        }
+        board.name = name;
+        board.rules = rules;
+        Ok(board)
+    }
+
+    /// Playable cells no blob can ever reach from the starting blobs.
+    fn unreachable_cells(&self) -> Positions {
+        let mut unreached = self.holes.invert();
+        let start = self.start[0].union_with(self.start[1]);
+        let mut stack: Vec<Position> = start.positions().collect();
+        while let Some(position) = stack.pop() {
+            if !unreached.contains(position) {
+                continue;
+            }
+            unreached.remove(Positions::single(position));
+            for distance in 0..2 {
+                stack.extend(&self.individual_neighbours[distance][position as usize]);
+            }
+        }
+        unreached
+    }
+
+    /// Iterate on rows of the board, each row being an iterator on its cells.
+    pub fn rows(&self) -> impl Iterator<Item = impl Iterator<Item = Position>> {
+        let width = self.width;
+        (0..self.height).map(move |y| (0..width).map(move |x| Position::from_2d(x, y)))
+    }
+
+    /// Is the board using the legacy 8x8 size ?
+    /// Other sizes are serialized with '/' between rows.
+    pub fn is_legacy(&self) -> bool {
+        self.width == 8 && self.height == 8
     }
 
     /// Pre-compute valid neighbours for each position.
     fn fill_individual_neighbours(&mut self) {
-        for position in 0i8..64i8 {
+        let (width, height) = (self.width as i8, self.height as i8);
+        for position in 0..CELLS {
             self.individual_neighbours[0].push(Vec::new());
             self.individual_neighbours[1].push(Vec::new());
-            let x = position % 8;
-            let y = position / 8;
-            for neighbouring_x in max(0, x - 2)..=min(7, x + 2) {
-                for neighbouring_y in max(0, y - 2)..=min(7, y + 2) {
+            let (x, y) = (position as Position).to_2d();
+            let (x, y) = (x as i8, y as i8);
+            if x >= width || y >= height {
+                continue;
+            }
+            for neighbouring_x in max(0, x - 2)..=min(width - 1, x + 2) {
+                for neighbouring_y in max(0, y - 2)..=min(height - 1, y + 2) {
                     let distance =
                         max((neighbouring_x - x).abs(), (neighbouring_y - y).abs()) as usize;
                     if distance != 0 {
                         let coordinate =
                             Position::from_2d(neighbouring_x as u8, neighbouring_y as u8);
                         if !self.holes.contains(coordinate) {
-                            self.individual_neighbours[distance - 1][position as usize]
-                                .push(coordinate);
+                            self.individual_neighbours[distance - 1][position].push(coordinate);
                         }
                     }
                 }
@@ -81,28 +521,206 @@ impl Board {
 
     /// Compute neighbours `Positions`.
     fn fill_neighbours(&mut self) {
-        for position in 0..64 {
-            self.neighbours.push(
-                self.individual_neighbours[0][position]
-                    .iter()
-                    .map(|p| Positions::single(*p))
-                    .fold(Default::default(), |a, b| a.union_with(b)),
-            );
+        for position in 0..CELLS {
+            self.neighbours.push(Positions::from_cells(
+                &self.individual_neighbours[0][position],
+            ));
         }
     }
 
     /// Deserialize serialized `Configuration` into `Board`.
-    pub fn deserialize(string: &str) -> Self {
-        let mut holes = 0;
-        let mut bit = 1u64;
-        for code in string.chars().skip(1) {
-            match code {
-                'h' => holes |= bit,
-                ' ' | 'r' | 'b' => {}
-                _ => panic!("invalid cell content"),
+    pub fn deserialize(string: &str) -> Result<Self, DeserializeError> {
+        let serialized = deserialize_cells(string)?;
+        let holes = Positions::from_cells(
+            &serialized
+                .cells
+                .iter()
+                .filter(|&&(_, _, code)| code == 'h')
+                .map(|&(position, _, _)| position)
+                .collect::<Vec<_>>(),
+        );
+        Ok(Board::with_size(serialized.width, serialized.height, holes))
+    }
+}
+
+/// Check a serialized `Configuration` and split it into its player and cells.
+/// Rows are separated by '/' ; without any, we have the legacy format of 8 rows of 8 cells.
+pub(crate) fn deserialize_cells(string: &str) -> Result<Serialized, DeserializeError> {
+    let mut chars = string.chars();
+    let current_player = match chars.next() {
+        Some('1') => true,
+        Some('0') => false,
+        Some(code) => return Err(DeserializeError::InvalidPlayer(code)),
+        None => return Err(DeserializeError::MissingPlayer),
+    };
+    let cells: Vec<char> = chars.collect();
+    let separated = cells.contains(&'/');
+    let rows: Vec<&[char]> = if separated {
+        cells.split(|&c| c == '/').collect()
+    } else if cells.len() == 64 {
+        cells.chunks(8).collect()
+    } else {
+        return Err(DeserializeError::WrongLength(cells.len()));
+    };
+    let (width, height) = (rows[0].len(), rows.len());
+    if let Some((row, cells)) = rows.iter().enumerate().find(|(_, r)| r.len() != width) {
+        return Err(DeserializeError::RowLength {
+            row,
+            expected: width,
+            found: cells.len(),
+        });
+    }
+    let side = 1..=MAX_SIDE as usize;
+    if !side.contains(&width) || !side.contains(&height) {
+        return Err(DeserializeError::WrongSize { width, height });
+    }
+    let mut serialized = Serialized {
+        current_player,
+        width: width as u8,
+        height: height as u8,
+        cells: Vec::with_capacity(width * height),
+    };
+    for (y, row) in rows.iter().enumerate() {
+        for (x, &character) in row.iter().enumerate() {
+            let index = 1 + y * (width + separated as usize) + x;
+            match character {
+                'r' | 'b' | 'h' | ' ' => {
+                    serialized
+                        .cells
+                        .push((Position::from_2d(x as u8, y as u8), index, character))
+                }
+                _ => return Err(DeserializeError::InvalidCell { index, character }),
             }
-            bit <<= 1;
         }
-        Board::new(Positions(holes))
+    }
+    Ok(serialized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::{Configuration, Movement};
+
+    #[test]
+    fn larger_boards_are_loaded_and_serialized() {
+        let board = Board::load("standard12").unwrap();
+        assert_eq!((board.width, board.height), (12, 12));
+        let state = Configuration::new(&board);
+        assert_eq!(state.empty_cells().count(), 144 - 4);
+        let serialized = state.serialize();
+        assert_eq!(serialized.len(), 1 + 12 * 13 - 1);
+        assert_eq!(&serialized[..14], "0r          b/");
+        let deserialized_board = Board::deserialize(&serialized).unwrap();
+        assert_eq!(deserialized_board.holes, board.holes);
+        let deserialized = Configuration::deserialize(&serialized, &deserialized_board).unwrap();
+        assert_eq!(deserialized.hash(), state.hash());
+        // legacy boards keep the legacy format
+        let board = Board::load("standard").unwrap();
+        assert_eq!(Configuration::new(&board).serialize().len(), 65);
+    }
+
+    #[test]
+    fn extended_format_declares_start_and_rules() {
+        let board = Board::load("duel").unwrap();
+        assert_eq!(board.name.as_deref(), Some("Duel"));
+        assert_eq!(board.rules.max_plies, Some(400));
+        assert!(board.holes.contains(0));
+        let red = Position::from_2d(2, 2);
+        assert!(board.start[0].contains(red) && board.start[0].len() == 2);
+        assert_eq!(Configuration::new(&board).blobs(true), board.start[1]);
+        // legacy files : corners and no metadata
+        let board = Board::load("standard").unwrap();
+        assert_eq!(board.name, None);
+        assert_eq!(board.rules, Rules::default());
+        assert_eq!(board.start, Board::default().start);
+    }
+
+    #[test]
+    fn built_in_boards_are_valid() {
+        for (name, _) in &BUILT_IN {
+            assert!(Board::load(name).is_ok(), "{} is invalid", name);
+        }
+        // files are found by path
+        assert_eq!(
+            Board::load("boards/duel").unwrap().name.as_deref(),
+            Some("Duel")
+        );
+        assert!(matches!(
+            Board::load("no/such/board"),
+            Err(BoardError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_boards_are_rejected() {
+        let error = |text: &str| Board::parse(text).err().unwrap().to_string();
+        assert_eq!(
+            Board::parse("").err().unwrap().to_string(),
+            "board has no rows"
+        );
+        assert_eq!(
+            error("\nxOO\nOOO"),
+            "line 2, column 1: starting cell is a hole (declare starting blobs with r and b)"
+        );
+        assert_eq!(error("OOO\nOO"), "line 2: row has 2 cells instead of 3");
+        assert!(matches!(
+            Board::parse("OOOO"),
+            Err(BoardError::SharedStart { line: 1, column: 1 })
+        ));
+        assert!(matches!(
+            Board::parse("O\nO"),
+            Err(BoardError::SharedStart { line: 1, column: 1 })
+        ));
+        let board = Board::parse("rOOb").unwrap();
+        assert_eq!(
+            crate::configuration::Configuration::new(&board).serialize(),
+            "0r  b"
+        );
+        assert_eq!(
+            error("OOO\nOzO"),
+            "line 2, column 2: unknown cell 'z' (expected x, O, ., r or b)"
+        );
+        assert_eq!(
+            error("rOOxxxOOOxxb"),
+            "line 1, column 7: cell cannot be reached from any starting blob"
+        );
+        assert!(matches!(
+            Board::parse(&"O".repeat(13)),
+            Err(BoardError::TooLarge {
+                line: 1,
+                column: 13
+            })
+        ));
+        assert!(matches!(
+            Board::parse("OrO\nOOO"),
+            Err(BoardError::MissingStart(true))
+        ));
+        for text in &[
+            "name: a\nOOO\nmax_plies: 3",
+            "size: 3\nOOO",
+            "max_plies: many\nOOO",
+        ] {
+            assert!(matches!(Board::parse(text), Err(BoardError::Header { .. })));
+        }
+        // jumps cross thin walls of holes
+        assert!(Board::parse("# comment\nOOxOO\n\nOOxOO").is_ok());
+        assert!(Board::parse("xrO\nObx").is_ok());
+    }
+
+    #[test]
+    fn moves_stay_on_the_board() {
+        let board = Board::with_size(10, 3, Positions::default());
+        let state = Configuration::new(&board);
+        // red's corners are a1 and j3, blue's j1 and a3
+        assert_eq!(state.serialize(), "0r        b/          /b        r");
+        for movement in state.movements() {
+            let destination = match movement {
+                Movement::Duplicate(destination) | Movement::Jump(_, destination) => destination,
+            };
+            let (x, y) = destination.to_2d();
+            assert!(x < 10 && y < 3);
+        }
+        // for each red blob : 3 duplicates and 4 jumps (the fifth cell is blue)
+        assert_eq!(state.movements().count(), 2 * (3 + 4));
     }
 }
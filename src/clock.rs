@@ -0,0 +1,116 @@
+//! Time control of games : players may have a time limit for each move and for the whole
+//! game.
+use std::cmp::min;
+use std::time::Duration;
+
+/// Players are only out of time once they exceed their time by more than that, to account for
+/// network latency and for stopping searches.
+pub const GRACE: Duration = Duration::from_millis(500);
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+/// Time allowed to each player. `None` means unlimited.
+pub struct TimeControl {
+    /// Maximum time spent on each move.
+    pub per_move: Option<Duration>,
+    /// Maximum time spent on all moves of a game.
+    pub per_game: Option<Duration>,
+}
+
+impl TimeControl {
+    /// Limit time spent on each move.
+    pub fn per_move(self, time: Duration) -> Self {
+        TimeControl {
+            per_move: Some(time),
+            ..self
+        }
+    }
+
+    /// Limit time spent on whole games.
+    pub fn per_game(self, time: Duration) -> Self {
+        TimeControl {
+            per_game: Some(time),
+            ..self
+        }
+    }
+}
+
+/// Time left to both players during a game.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    control: TimeControl,
+    spent: [Duration; 2],
+}
+
+impl Clock {
+    /// Start a game with given time control.
+    pub fn new(control: TimeControl) -> Self {
+        Clock {
+            control,
+            spent: Default::default(),
+        }
+    }
+
+    /// Time left to given player for the rest of the game (`None` if unlimited).
+    pub fn remaining(&self, player: bool) -> Option<Duration> {
+        self.control
+            .per_game
+            .map(|per_game| per_game.saturating_sub(self.spent[player as usize]))
+    }
+
+    /// Time given player may spend on its next move (`None` if unlimited).
+    pub fn allowed(&self, player: bool) -> Option<Duration> {
+        match (self.control.per_move, self.remaining(player)) {
+            (Some(per_move), Some(remaining)) => Some(min(per_move, remaining)),
+            (per_move, remaining) => per_move.or(remaining),
+        }
+    }
+
+    /// Account for time spent by given player on a move.
+    /// Return false if the move took more than the time per move or if the player spent more
+    /// than the time per game, grace included. The grace is given only once for the whole game.
+    pub fn spend(&mut self, player: bool, elapsed: Duration) -> bool {
+        let spent = &mut self.spent[player as usize];
+        *spent += elapsed;
+        let move_in_time = self
+            .control
+            .per_move
+            .is_none_or(|per_move| elapsed <= per_move + GRACE);
+        let game_in_time = self
+            .control
+            .per_game
+            .is_none_or(|per_game| *spent <= per_game + GRACE);
+        move_in_time && game_in_time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clocks_run_down() {
+        let second = Duration::from_secs(1);
+        let mut clock = Clock::new(
+            TimeControl::default()
+                .per_move(2 * second)
+                .per_game(3 * second),
+        );
+        assert_eq!(clock.allowed(false), Some(2 * second));
+        assert!(clock.spend(false, 2 * second));
+        assert_eq!(clock.allowed(false), Some(second));
+        assert_eq!(clock.allowed(true), Some(2 * second));
+        assert!(clock.spend(false, second + GRACE));
+        assert!(!clock.spend(false, GRACE + Duration::from_millis(1)));
+        assert_eq!(clock.remaining(false), Some(Duration::ZERO));
+        // the grace is not given again on each move
+        let mut clock = Clock::new(TimeControl::default().per_game(second));
+        for _ in 0..3 {
+            assert!(clock.spend(true, second / 3));
+        }
+        assert!(clock.spend(true, GRACE));
+        assert!(!clock.spend(true, GRACE / 10));
+        let mut unlimited = Clock::new(TimeControl::default());
+        assert!(unlimited.spend(true, 1000 * second));
+        assert_eq!(unlimited.allowed(true), None);
+    }
+}
@@ -1,10 +1,16 @@
 //! Provide a `Configuration` for storing game state ; a `Movement` for storing moves to play.
-use super::board::Board;
-use super::positions::{BoardPosition, Position, Positions};
-use super::strategy::Strategy;
+use super::board::{deserialize_cells, Board, DeserializeError};
+use super::clock::{Clock, TimeControl};
+use super::history::History;
+use super::positions::{BoardPosition, Position, Positions, CELLS, MAX_SIDE};
+use super::record::{Forfeit, ForfeitReason, GameRecord, GameResult, RecordedMove};
+use super::strategy::{Decision, SearchContext, SearchLimits, Strategy};
+use super::zobrist;
 use serde_derive::{Deserialize, Serialize};
+use std::error::Error;
 use std::fmt;
 use std::iter::once;
+use std::str::FromStr;
 use term;
 
 #[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
@@ -16,6 +22,133 @@ pub enum Movement {
     Jump(u8, u8),
 }
 
+/// Name of given player.
+fn player_name(player: bool) -> &'static str {
+    ["red", "blue"][player as usize]
+}
+
+/// Write given `Position` in algebraic notation : column letter then row number (from 1).
+fn write_cell(f: &mut fmt::Formatter, position: Position) -> fmt::Result {
+    let (x, y) = position.to_2d();
+    write!(f, "{}{}", (b'a' + x) as char, y + 1)
+}
+
+/// Parse a `Position` in algebraic notation.
+fn parse_cell(cell: &str) -> Option<Position> {
+    let mut chars = cell.chars();
+    let column = chars.next()?;
+    let row: u8 = chars.as_str().parse().ok()?;
+    if !column.is_ascii_lowercase() || !(1..=MAX_SIDE).contains(&row) {
+        return None;
+    }
+    let column = column as u8 - b'a';
+    if column >= MAX_SIDE {
+        return None;
+    }
+    Some(Position::from_2d(column, row - 1))
+}
+
+/// Movements are written in algebraic notation : destination cell for duplicates (`c3`),
+/// source and destination cells separated by a dash for jumps (`a1-c3`).
+impl fmt::Display for Movement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Movement::Duplicate(destination) => write_cell(f, destination),
+            Movement::Jump(source, destination) => {
+                write_cell(f, source)?;
+                write!(f, "-")?;
+                write_cell(f, destination)
+            }
+        }
+    }
+}
+
+/// Number of columns of the grid legacy peers number cells in.
+const LEGACY_SIDE: u8 = 8;
+
+impl Movement {
+    /// Same move with its cells numbered like legacy peers do, in an 8 x 8 grid.
+    /// `None` if a cell does not fit in it.
+    pub fn to_legacy(self) -> Option<Movement> {
+        self.map_cells(|cell| {
+            let (x, y) = cell.to_2d();
+            (x < LEGACY_SIDE && y < LEGACY_SIDE).then_some(y * LEGACY_SIDE + x)
+        })
+    }
+
+    /// Move received from a legacy peer, its cells numbered in an 8 x 8 grid.
+    /// `None` if a cell does not fit in it.
+    pub fn from_legacy(legacy: Movement) -> Option<Movement> {
+        legacy.map_cells(|cell| {
+            (cell < LEGACY_SIDE * LEGACY_SIDE)
+                .then(|| Position::from_2d(cell % LEGACY_SIDE, cell / LEGACY_SIDE))
+        })
+    }
+
+    fn map_cells<F: Fn(Position) -> Option<Position>>(self, convert: F) -> Option<Movement> {
+        Some(match self {
+            Movement::Duplicate(destination) => Movement::Duplicate(convert(destination)?),
+            Movement::Jump(source, destination) => {
+                Movement::Jump(convert(source)?, convert(destination)?)
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Error returned when parsing an invalid `Movement`.
+pub struct ParseMovementError(String);
+
+impl fmt::Display for ParseMovementError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid movement '{}' (expected c3 or a1-c3)", self.0)
+    }
+}
+
+impl Error for ParseMovementError {}
+
+/// Parse algebraic notation. Cells one apart separated by a dash are also accepted as
+/// duplicates.
+impl FromStr for Movement {
+    type Err = ParseMovementError;
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let error = || ParseMovementError(string.to_owned());
+        let string = string.trim();
+        match string.split_once('-') {
+            None => parse_cell(string).map(Movement::Duplicate).ok_or_else(error),
+            Some((source, destination)) => {
+                let source = parse_cell(source).ok_or_else(error)?;
+                let destination = parse_cell(destination).ok_or_else(error)?;
+                match source.distance_to(destination) {
+                    1 => Ok(Movement::Duplicate(destination)),
+                    2 => Ok(Movement::Jump(source, destination)),
+                    _ => Err(error()),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+/// What `apply_movement` (or `apply_skip`) changed : enough to take it back.
+pub struct Undo {
+    /// Who played.
+    player: bool,
+    /// Where a jumping blob came from.
+    source: Option<Position>,
+    /// Where the new blob is (`None` for skips).
+    destination: Option<Position>,
+    /// Enemy blobs which were converted.
+    converted: Positions,
+}
+
+impl Undo {
+    /// Who played the move (or skipped).
+    pub fn player(&self) -> bool {
+        self.player
+    }
+}
+
 #[derive(Copy, Clone)]
 /// Game state. We know, who should play, what is the board and where every blob is located.
 pub struct Configuration<'a> {
@@ -23,31 +156,42 @@ pub struct Configuration<'a> {
     board: &'a Board,
     /// Who should play now.
     pub current_player: bool,
+    /// Zobrist hash of blobs and player, incrementally updated.
+    hash: u64,
 }
 
 impl<'a> Configuration<'a> {
     /// Create an initial game configuration out of given `Board`.
+    /// Blobs are on the board's starting positions.
     pub fn new(board: &'a Board) -> Self {
+        let blobs = board.start;
         Configuration {
-            blobs: [
-                Positions::single(0).union_with(Positions::single(63)),
-                Positions::single(7).union_with(Positions::single(56)),
-            ],
+            blobs,
             board,
             current_player: false,
+            hash: zobrist::hash(&blobs, false),
         }
     }
 
+    /// Return the zobrist hash of the configuration (blobs and player to move).
+    /// Holes are not taken into account so hashes should only be compared between
+    /// configurations on the same `Board`.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
     /// Play given move on self.
-    pub fn apply_movement(&mut self, movement: &Movement) {
+    /// Return what is needed to take it back with `undo_movement`.
+    pub fn apply_movement(&mut self, movement: &Movement) -> Undo {
         let me = self.current_player as usize;
         let him = !self.current_player as usize;
-        let destination = match *movement {
+        let (source, destination) = match *movement {
             Movement::Jump(source, destination) => {
                 self.blobs[me].remove(Positions::single(source));
-                destination
+                self.hash ^= zobrist::blob_key(me, source);
+                (Some(source), destination)
             }
-            Movement::Duplicate(destination) => destination,
+            Movement::Duplicate(destination) => (None, destination),
         };
         let adversary_blobs = self.blobs[him];
         let changing_blobs =
@@ -55,7 +199,50 @@ impl<'a> Configuration<'a> {
         self.blobs[me].add(changing_blobs);
         self.blobs[me].add(Positions::single(destination));
         self.blobs[him].remove(changing_blobs);
+        self.hash ^= zobrist::blobs_key(me, changing_blobs)
+            ^ zobrist::blobs_key(him, changing_blobs)
+            ^ zobrist::blob_key(me, destination);
+        Undo {
+            destination: Some(destination),
+            source,
+            converted: changing_blobs,
+            ..self.apply_skip()
+        }
+    }
+
+    /// Skip turn on self.
+    /// Return what is needed to take it back with `undo_movement`.
+    pub fn apply_skip(&mut self) -> Undo {
+        let undo = Undo {
+            player: self.current_player,
+            source: None,
+            destination: None,
+            converted: Positions::default(),
+        };
         self.current_player = !self.current_player;
+        self.hash ^= zobrist::PLAYER_KEY;
+        undo
+    }
+
+    /// Take back the move (or skip) which returned given `Undo`.
+    /// Moves must be taken back in reverse order of play.
+    pub fn undo_movement(&mut self, undo: &Undo) {
+        self.current_player = undo.player;
+        self.hash ^= zobrist::PLAYER_KEY;
+        if let Some(destination) = undo.destination {
+            let me = undo.player as usize;
+            let him = !undo.player as usize;
+            self.blobs[me].remove(Positions::single(destination));
+            self.blobs[me].remove(undo.converted);
+            self.blobs[him].add(undo.converted);
+            self.hash ^= zobrist::blobs_key(me, undo.converted)
+                ^ zobrist::blobs_key(him, undo.converted)
+                ^ zobrist::blob_key(me, destination);
+            if let Some(source) = undo.source {
+                self.blobs[me].add(Positions::single(source));
+                self.hash ^= zobrist::blob_key(me, source);
+            }
+        }
     }
 
     /// Create a new `Configuration` by playing given `Movement` on self.
@@ -68,7 +255,7 @@ impl<'a> Configuration<'a> {
     /// Create a new `Configuration` by skipping turn on self.
     pub fn skip_play(&self) -> Self {
         let mut new_configuration = *self;
-        new_configuration.current_player = !new_configuration.current_player;
+        new_configuration.apply_skip();
         new_configuration
     }
 
@@ -79,27 +266,54 @@ impl<'a> Configuration<'a> {
 
     /// Iterate on all `Position`(s) of empty cells.
     pub fn empty_cells(&self) -> impl Iterator<Item = Position> {
+        self.empty_positions().positions()
+    }
+
+    /// `Positions` of all empty cells.
+    pub(crate) fn empty_positions(&self) -> Positions {
         self.blobs[0]
             .union_with(self.blobs[1])
             .union_with(self.board.holes)
             .invert()
-            .positions()
+    }
+
+    /// `Positions` of given player's blobs.
+    pub(crate) fn blobs(&self, player: bool) -> Positions {
+        self.blobs[player as usize]
+    }
+
+    /// The `Board` we play on.
+    pub fn board(&self) -> &'a Board {
+        self.board
     }
 
     /// Return the configuration value (#other_player - #current_player)
-    pub fn value(&self) -> i8 {
+    pub fn value(&self) -> i16 {
         if self.current_player {
-            (self.blobs[0].len() - self.blobs[1].len()) as i8
+            self.blobs[0].len() - self.blobs[1].len()
         } else {
-            -(self.blobs[0].len() - self.blobs[1].len()) as i8
+            -(self.blobs[0].len() - self.blobs[1].len())
         }
     }
 
+    /// Return how many enemy blobs given movement converts.
+    pub fn converted_blobs(&self, movement: &Movement) -> i16 {
+        let destination = match *movement {
+            Movement::Jump(_, destination) | Movement::Duplicate(destination) => destination,
+        };
+        self.blobs[!self.current_player as usize]
+            .intersection_with(self.board.neighbours[destination as usize])
+            .len()
+    }
+
     /// Return if given movement is correct for current configuration.
     pub fn check_move(&self, movement: &Movement) -> bool {
+        let in_grid = |position| (position as usize) < CELLS;
         let destination = match *movement {
             Movement::Jump(source, destination) => {
-                if !(self.blobs[self.current_player as usize].contains(source))
+                if !in_grid(source)
+                    || !in_grid(destination)
+                    || !(self.blobs[self.current_player as usize].contains(source))
                     || source.distance_to(destination) != 2
                 {
                     return false;
@@ -107,7 +321,8 @@ impl<'a> Configuration<'a> {
                 destination
             }
             Movement::Duplicate(destination) => {
-                if self.board.neighbours[destination as usize]
+                if !in_grid(destination)
+                    || self.board.neighbours[destination as usize]
                     .intersection_with(self.blobs[self.current_player as usize])
                     .is_empty()
                 {
@@ -122,38 +337,145 @@ impl<'a> Configuration<'a> {
     }
 
     /// Play a match between the given players starting from current `Configuration`.
-    pub fn battle<T: Strategy, U: Strategy>(&mut self, mut player_one: T, mut player_two: U) {
+    /// Return the record of the game.
+    pub fn battle<T: Strategy, U: Strategy>(&mut self, player_one: T, player_two: U) -> GameRecord {
+        self.battle_with_clock(Default::default(), player_one, player_two)
+    }
+
+    /// Play a match between the given players starting from current `Configuration`, under
+    /// given `TimeControl`. Each search is limited to the time the player has left.
+    /// Players exceeding their time, playing illegal moves or giving up (see `Decision`) lose
+    /// by forfeit.
+    /// Return the record of the game.
+    pub fn battle_with_clock<T: Strategy, U: Strategy>(
+        &mut self,
+        time_control: TimeControl,
+        player_one: T,
+        player_two: U,
+    ) -> GameRecord {
+        self.play_battle(time_control, player_one, player_two, true)
+    }
+
+    /// Same as `battle_with_clock` but without displaying the game, for running many games
+    /// at once.
+    pub fn quiet_battle<T: Strategy, U: Strategy>(
+        &mut self,
+        time_control: TimeControl,
+        player_one: T,
+        player_two: U,
+    ) -> GameRecord {
+        self.play_battle(time_control, player_one, player_two, false)
+    }
+
+    fn play_battle<T: Strategy, U: Strategy>(
+        &mut self,
+        time_control: TimeControl,
+        mut player_one: T,
+        mut player_two: U,
+        verbose: bool,
+    ) -> GameRecord {
+        macro_rules! display {
+            ($($argument:tt)*) => {
+                if verbose {
+                    println!($($argument)*)
+                }
+            };
+        }
+        let start = self.serialize();
+        let mut history = History::default();
+        let mut times = Vec::new();
+        let max_plies = self.board.rules.max_plies;
+        let mut clock = Clock::new(time_control);
+        let mut forfeit = None;
         while !self.game_over() {
-            println!(
+            if max_plies.is_some_and(|max_plies| history.len() as u32 >= max_plies) {
+                display!("maximal number of plies reached");
+                break;
+            }
+            display!(
                 "{} player's turn (he is losing by {} before playing)",
-                ["red", "blue"][self.current_player as usize],
+                player_name(self.current_player),
                 self.value()
             );
-            println!("{}", self);
-            let play_attempt = if self.current_player {
-                player_two.compute_next_move(self, None)
-            } else {
-                player_one.compute_next_move(self, None)
+            display!("{}", self);
+            let player = self.current_player;
+            let limits = SearchLimits {
+                time: clock.allowed(player),
+                plies: max_plies.map(|max_plies| max_plies - history.len() as u32),
+                ..Default::default()
             };
-            if let Some(ref next_move) = play_attempt {
-                assert!(self.check_move(next_move));
-                self.apply_movement(next_move);
+            let mut context = SearchContext::new(limits);
+            let decision = if player {
+                player_two.decide(self, &mut context)
             } else {
-                self.current_player = !self.current_player;
+                player_one.decide(self, &mut context)
+            };
+            let elapsed = context.elapsed();
+            let in_time = clock.spend(player, elapsed);
+            let reason = match decision {
+                _ if !in_time => Some(ForfeitReason::Timeout),
+                Decision::Play(Some(movement)) if !self.check_move(&movement) => {
+                    Some(ForfeitReason::IllegalMove)
+                }
+                // passing is only allowed without any move left
+                Decision::Play(None) if self.can_move() => Some(ForfeitReason::IllegalMove),
+                Decision::Forfeit(reason) => Some(reason),
+                _ => None,
+            };
+            if let Some(reason) = reason {
+                display!("{} forfeits ({})", player_name(player), reason);
+                forfeit = Some(Forfeit { player, reason });
+                break;
+            }
+            times.push(elapsed.as_millis() as u64);
+            match decision {
+                Decision::Play(play_attempt) => {
+                    if let Some(ref next_move) = play_attempt {
+                        display!("{} plays {}", player_name(self.current_player), next_move);
+                    } else {
+                        display!("{} passes", player_name(self.current_player));
+                    }
+                    history.play(self, play_attempt);
+                }
+                Decision::TakeBack => {
+                    if !history.take_back_turn(self, self.current_player) {
+                        display!("nothing to take back");
+                    }
+                    times.truncate(history.len());
+                }
+                Decision::Forfeit(_) => unreachable!("forfeits end the game"),
             }
         }
 
-        let value = self.blobs[0].len() - self.blobs[1].len();
-        match value {
-            x if x > 0 => println!("RED ({}) wins over BLUE ({})!", player_one, player_two),
-            x if x < 0 => println!("BLUE ({}) wins over RED ({})!", player_two, player_one),
-            _ => println!("DRAW!"),
+        let result = GameResult {
+            red: self.blobs[0].len(),
+            blue: self.blobs[1].len(),
+            forfeit,
+        };
+        match result.winner() {
+            Some(false) => display!("RED ({}) wins over BLUE ({})!", player_one, player_two),
+            Some(true) => display!("BLUE ({}) wins over RED ({})!", player_two, player_one),
+            None => display!("DRAW!"),
+        }
+        display!("{}", self);
+        display!("GAME OVER (red value of {})", result.red - result.blue);
+        player_one.game_over(&result);
+        player_two.game_over(&result);
+        GameRecord {
+            board: self.board.name.clone(),
+            start,
+            max_plies,
+            players: [player_one.to_string(), player_two.to_string()],
+            moves: history
+                .moves()
+                .zip(times)
+                .map(|(movement, millis)| RecordedMove { movement, millis })
+                .collect(),
+            result: Some(result),
         }
-        println!("{}", self);
-        println!("GAME OVER (red value of {})", value);
     }
 
-    /// Return true if no empty space remains or someone died.
+    /// Return true if no empty space remains, someone died or nobody can move anymore.
     pub fn game_over(&self) -> bool {
         self.blobs[0].is_empty()
             || self.blobs[1].is_empty()
@@ -161,11 +483,12 @@ impl<'a> Configuration<'a> {
                 .union_with(self.blobs[1])
                 .union_with(self.board.holes)
                 .is_all()
+            || (!self.can_move() && !self.skip_play().can_move())
     }
 
     /// Return if the current player can do any movements
     pub fn can_move(&self) -> bool {
-        self.movements().count() != 0
+        self.movements().next().is_some()
     }
 
     /// Iterate on all possible jumps for given player.
@@ -197,94 +520,398 @@ impl<'a> Configuration<'a> {
         self.duplicates().chain(self.jumps())
     }
 
+    /// What is on given cell : 'h' for holes, 'r' and 'b' for blobs, ' ' if empty.
+    fn cell_code(&self, position: Position) -> char {
+        match (
+            self.board.holes.contains(position),
+            self.blobs[0].contains(position),
+            self.blobs[1].contains(position),
+        ) {
+            (true, false, false) => 'h',
+            (false, true, false) => 'r',
+            (false, false, true) => 'b',
+            (false, false, false) => ' ',
+            _ => panic!("invalid configuration"),
+        }
+    }
+
     /// Serialize `Configuration` into a `String`.
     /// Use in communications with sub-processes.
+    /// The player code is followed by all cells, row by row. Rows are separated by '/' unless
+    /// the board has the legacy 8x8 size.
     pub fn serialize(&self) -> String {
-        once(if self.current_player { '1' } else { '0' })
-            .chain(
+        let separator = if self.board.is_legacy() { "" } else { "/" };
+        once(if self.current_player { '1' } else { '0' }.to_string())
+            .chain(once(
                 self.board
-                    .holes
-                    .full_bits()
-                    .zip(self.blobs[0].full_bits().zip(self.blobs[1].full_bits()))
-                    .map(|(h, (r, b))| match (h, r, b) {
-                        (true, false, false) => 'h',
-                        (false, true, false) => 'r',
-                        (false, false, true) => 'b',
-                        (false, false, false) => ' ',
-                        _ => panic!("invalid configuration"),
-                    }),
-            )
+                    .rows()
+                    .map(|row| row.map(|position| self.cell_code(position)).collect::<String>())
+                    .collect::<Vec<_>>()
+                    .join(separator),
+            ))
             .collect()
     }
 
     /// Deserialize given `String` into a `Configuration`. You need to deserialize the `Board`
     /// first.
-    pub fn deserialize(string: &str, board: &'a Board) -> Self {
-        let mut chars = string.chars();
-        let current_player;
-        if let Some(player_char) = chars.next() {
-            current_player = match player_char {
-                '1' => true,
-                '0' => false,
-                _ => panic!("invalid player code"),
-            }
-        } else {
-            panic!("missing player code");
+    /// Malformed strings, or strings not matching the board, are rejected with an error.
+    pub fn deserialize(string: &str, board: &'a Board) -> Result<Self, DeserializeError> {
+        let serialized = deserialize_cells(string)?;
+        if (serialized.width, serialized.height) != (board.width, board.height) {
+            return Err(DeserializeError::SizeMismatch {
+                found: (serialized.width, serialized.height),
+                expected: (board.width, board.height),
+            });
         }
-        let mut blobs = [0; 2];
-        let mut bit = 1u64;
-        for code in chars {
-            match code {
-                'r' => blobs[0] |= bit,
-                'b' => blobs[1] |= bit,
-                ' ' | 'h' => {}
-                _ => panic!("invalid cell content"),
+        let mut blobs = [Positions::default(); 2];
+        for (position, index, code) in serialized.cells {
+            let player = match code {
+                'r' => false,
+                'b' => true,
+                _ => continue,
+            };
+            if board.holes.contains(position) {
+                return Err(DeserializeError::BlobOnHole(index));
             }
-            bit <<= 1;
+            blobs[player as usize].add(Positions::single(position));
         }
-        Configuration {
+        let current_player = serialized.current_player;
+        Ok(Configuration {
             board,
-            blobs: [Positions(blobs[0]), Positions(blobs[1])],
+            blobs,
             current_player,
-        }
+            hash: zobrist::hash(&blobs, current_player),
+        })
     }
 }
 
 impl<'a> fmt::Display for Configuration<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "\n  01234567 \n")?;
-        write!(f, " +--------+\n")?;
-        for (index, (hole, (red, blue))) in self
-            .board
-            .holes
-            .full_bits()
-            .zip(self.blobs[0].full_bits().zip(self.blobs[1].full_bits()))
-            .enumerate()
-        {
-            if index % 8 == 0 {
-                write!(f, "{}|", index / 8)?;
+        let columns: String = (0..self.board.width).map(|x| (b'a' + x) as char).collect();
+        let border = "-".repeat(self.board.width as usize);
+        writeln!(f, "\n   {} ", columns)?;
+        writeln!(f, "  +{}+", border)?;
+        for (y, row) in self.board.rows().enumerate() {
+            write!(f, "{:>2}|", y + 1)?;
+            for position in row {
+                let mut t = term::stdout().unwrap();
+                match self.cell_code(position) {
+                    'h' => write!(f, "x")?,
+                    'r' => {
+                        t.fg(term::color::RED).unwrap();
+                        write!(f, "x")?;
+                        t.reset().unwrap();
+                    }
+                    'b' => {
+                        t.fg(term::color::CYAN).unwrap();
+                        write!(f, "o")?;
+                        t.reset().unwrap();
+                    }
+                    _ => write!(f, " ")?,
+                }
             }
-            let mut t = term::stdout().unwrap();
-            match (hole, red, blue) {
-                (true, false, false) => write!(f, "x")?,
-                (false, true, false) => {
-                    t.fg(term::color::RED).unwrap();
-                    write!(f, "x")?;
-                    t.reset().unwrap();
+            writeln!(f, "|")?;
+        }
+        write!(f, "  +{}+", border)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::GRACE;
+    use crate::evaluation::Material;
+    use crate::fixtures::{red_stuck, three_cells_board};
+    use crate::strategy::mcts::Random;
+    use crate::strategy::Greedy;
+    use std::time::Duration;
+
+    #[test]
+    fn movements_notation_round_trips() {
+        let board = Board::with_size(MAX_SIDE, MAX_SIDE, Positions::default());
+        for y in 0..MAX_SIDE {
+            for x in 0..MAX_SIDE {
+                let position = Position::from_2d(x, y);
+                assert_eq!(position.to_2d(), (x, y));
+                let duplicate = Movement::Duplicate(position);
+                let written = duplicate.to_string();
+                assert_eq!(written, format!("{}{}", (b'a' + x) as char, y + 1));
+                assert_eq!(written.parse(), Ok(duplicate));
+                for &destination in &board.individual_neighbours[1][position as usize] {
+                    let jump = Movement::Jump(position, destination);
+                    assert_eq!(jump.to_string().parse(), Ok(jump));
                 }
-                (false, false, true) => {
-                    t.fg(term::color::CYAN).unwrap();
-                    write!(f, "o")?;
-                    t.reset().unwrap();
+            }
+        }
+        assert_eq!("a1-c3".parse(), Ok(Movement::Jump(0, Position::from_2d(2, 2))));
+        assert_eq!("l12".parse(), Ok(Movement::Duplicate(143)));
+        assert_eq!("a1-b2".parse(), Ok(Movement::Duplicate(13)));
+        for invalid in &["", "a", "m1", "a0", "a13", "a1-a4", "a1-", "1a", "a1-c3-e5"] {
+            assert!(invalid.parse::<Movement>().is_err(), "{} parsed", invalid);
+        }
+    }
+
+    #[test]
+    fn legacy_movements_use_eight_columns() {
+        // as sent by baseline peers
+        let jump: Option<Movement> = serde_json::from_str(r#"{"Jump":[0,18]}"#).unwrap();
+        let jump = Movement::from_legacy(jump.unwrap()).unwrap();
+        assert_eq!(jump, "a1-c3".parse().unwrap());
+        assert_eq!(jump.to_legacy(), Some(Movement::Jump(0, 18)));
+        let duplicate: Movement = "h8".parse().unwrap();
+        let legacy = Movement::Duplicate(63);
+        assert_eq!(duplicate.to_legacy(), Some(legacy));
+        assert_eq!(Movement::from_legacy(legacy), Some(duplicate));
+        assert_eq!(Movement::from_legacy(Movement::Duplicate(64)), None);
+        assert_eq!("i1".parse::<Movement>().unwrap().to_legacy(), None);
+    }
+
+    #[test]
+    fn game_is_over_when_nobody_can_move() {
+        let board = three_cells_board();
+        let state = red_stuck(&board);
+
+        // red is stuck but blue can still jump
+        assert!(!state.can_move());
+        assert!(!state.game_over());
+        let state = state.skip_play().play(&Movement::Jump(1, 3));
+        assert!(!state.game_over());
+        // without cell 3 nobody can move
+        let board = Board::new(Positions::from_cells(&[0, 1]).invert());
+        let stuck = red_stuck(&board);
+
+        assert!(stuck.game_over());
+    }
+
+    #[test]
+    fn incremental_hash_matches_full_hash() {
+        let board = Board::default();
+        let mut configuration = Configuration::new(&board);
+        for turn in 0..40 {
+            let movement = configuration.movements().nth(turn % 7);
+            if let Some(movement) = movement {
+                configuration.apply_movement(&movement);
+            } else {
+                configuration.apply_skip();
+            }
+            assert_eq!(
+                configuration.hash(),
+                zobrist::hash(&configuration.blobs, configuration.current_player)
+            );
+            let deserialized =
+                Configuration::deserialize(&configuration.serialize(), &board).unwrap();
+            assert_eq!(configuration.hash(), deserialized.hash());
+        }
+    }
+
+    #[test]
+    fn hash_depends_on_player() {
+        let board = Board::default();
+        let configuration = Configuration::new(&board);
+        assert_ne!(configuration.hash(), configuration.skip_play().hash());
+        assert_eq!(
+            configuration.hash(),
+            configuration.skip_play().skip_play().hash()
+        );
+    }
+
+    /// Player sleeping for given time then playing given move.
+    struct Cheater(Duration, Option<Movement>);
+
+    impl fmt::Display for Cheater {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "Cheater")
+        }
+    }
+
+    impl Strategy for Cheater {
+        fn compute_next_move(
+            &mut self,
+            _: &Configuration,
+            _: &mut SearchContext,
+        ) -> Option<Movement> {
+            std::thread::sleep(self.0);
+            self.1
+        }
+    }
+
+    /// Greedy player taking given time on each move.
+    struct Slow(Duration);
+
+    impl fmt::Display for Slow {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "Slow")
+        }
+    }
+
+    impl Strategy for Slow {
+        fn compute_next_move(
+            &mut self,
+            state: &Configuration,
+            context: &mut SearchContext,
+        ) -> Option<Movement> {
+            std::thread::sleep(self.0);
+            Greedy(Material).compute_next_move(state, context)
+        }
+    }
+
+    #[test]
+    fn slow_moves_exhaust_the_game_time() {
+        let board = Board::default();
+        // each move fits in the grace period but all of them do not fit in the game time
+        let time_control = TimeControl::default().per_game(GRACE);
+        let record = Configuration::new(&board).battle_with_clock(
+            time_control,
+            Slow(GRACE * 3 / 5),
+            Greedy(Material),
+        );
+        let result = record.result.unwrap();
+        assert_eq!(
+            result.forfeit,
+            Some(Forfeit {
+                player: false,
+                reason: ForfeitReason::Timeout
+            })
+        );
+        // the fourth move of red is the first one exceeding the game time and its grace
+        assert_eq!(record.moves.len(), 6);
+        assert_eq!(record.validate(), Ok(()));
+    }
+
+    #[test]
+    fn illegal_or_late_moves_forfeit() {
+        let board = Board::default();
+        let time_control = TimeControl::default().per_game(Duration::from_millis(10));
+        let cases = [
+            (
+                Cheater(Duration::ZERO, Some(Movement::Duplicate(50))),
+                Default::default(),
+                ForfeitReason::IllegalMove,
+            ),
+            (
+                Cheater(Duration::ZERO, None),
+                Default::default(),
+                ForfeitReason::IllegalMove,
+            ),
+            (
+                Cheater(GRACE * 2, Some(Movement::Duplicate(1))),
+                time_control,
+                ForfeitReason::Timeout,
+            ),
+        ];
+        for (cheater, time_control, reason) in cases {
+            let record = Configuration::new(&board).battle_with_clock(
+                time_control,
+                cheater,
+                Greedy(Material),
+            );
+            let result = record.result.unwrap();
+            assert_eq!(
+                result.forfeit,
+                Some(Forfeit {
+                    player: false,
+                    reason
+                })
+            );
+            assert_eq!(result.winner(), Some(true));
+            assert!(record.moves.is_empty());
+            assert_eq!(record.validate(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn malformed_configurations_are_rejected() {
+        let board = Board::default();
+        let empty = " ".repeat(64);
+        let errors = [
+            ("".to_owned(), DeserializeError::MissingPlayer),
+            (format!("2{}", empty), DeserializeError::InvalidPlayer('2')),
+            (
+                format!("0{}", &empty[1..]),
+                DeserializeError::WrongLength(63),
+            ),
+            (
+                format!("0r{}x", &empty[2..]),
+                DeserializeError::InvalidCell {
+                    index: 64,
+                    character: 'x',
+                },
+            ),
+            (
+                "0rb/r/ b".to_owned(),
+                DeserializeError::RowLength {
+                    row: 1,
+                    expected: 2,
+                    found: 1,
+                },
+            ),
+            (
+                format!("0{}", ["r"; 13].join("/")),
+                DeserializeError::WrongSize {
+                    width: 1,
+                    height: 13,
+                },
+            ),
+            (
+                "0r b/ b ".to_owned(),
+                DeserializeError::SizeMismatch {
+                    found: (3, 2),
+                    expected: (8, 8),
+                },
+            ),
+        ];
+        for (string, error) in &errors {
+            assert_eq!(
+                Configuration::deserialize(string, &board).err(),
+                Some(*error)
+            );
+        }
+        let board = Board::new(Positions::single(Position::from_2d(1, 1)));
+        let blob_on_hole = format!("1{}b{}", &empty[..9], &empty[10..]);
+        assert_eq!(
+            Configuration::deserialize(&blob_on_hole, &board).err(),
+            Some(DeserializeError::BlobOnHole(10))
+        );
+    }
+
+    #[test]
+    fn deserializing_random_strings_never_panics() {
+        let mut random = Random(0x2545_f491_4f6c_dd1d);
+        let boards = [Board::default(), Board::load("duel").unwrap()];
+        let alphabet: Vec<char> = "01rbh /x\né".chars().collect();
+        for round in 0..2000 {
+            let mut string: Vec<char> = Configuration::new(&boards[round % 2])
+                .serialize()
+                .chars()
+                .collect();
+            // a few random edits : replacements, insertions, removals and truncations
+            for _ in 0..random.below(4) {
+                let index = random.below(string.len() + 1);
+                let character = alphabet[random.below(alphabet.len())];
+                match random.below(4) {
+                    0 if index < string.len() => string[index] = character,
+                    1 => string.insert(index, character),
+                    2 if index < string.len() => {
+                        string.remove(index);
+                    }
+                    _ => string.truncate(index),
                 }
-                (false, false, false) => write!(f, " ")?,
-                _ => panic!("invalid board: {} {} {}", hole, red, blue),
             }
-            if index % 8 == 7 {
-                write!(f, "|\n")?;
+            let string: String = string.into_iter().collect();
+            for board in &boards {
+                let _ = Configuration::deserialize(&string, board);
+            }
+            // whatever is accepted must round trip
+            if let Ok(board) = Board::deserialize(&string) {
+                let state = Configuration::deserialize(&string, &board).unwrap();
+
+                let serialized = state.serialize();
+                assert_eq!(Board::deserialize(&serialized).unwrap().holes, board.holes);
+                let again = Configuration::deserialize(&serialized, &board).unwrap();
+
+                assert_eq!(again.hash(), state.hash());
             }
         }
-        write!(f, " +--------+")?;
-        Ok(())
     }
 }
@@ -0,0 +1,219 @@
+//! Evaluation functions : how good is a `Configuration` ?
+//! All evaluations are given from the point of view of the player who just moved (like
+//! `Configuration::value`), so they can be plugged directly into our negamax searches.
+//! They only give heuristic scores : finished games are scored by the searches themselves.
+use super::configuration::Configuration;
+use super::positions::Positions;
+use super::score::Score;
+use std::fmt;
+
+/// Something able to evaluate configurations.
+pub trait Evaluator: fmt::Display {
+    /// Evaluate given `Configuration` for the player who just moved
+    /// (the opponent of `state.current_player`).
+    fn evaluate(&self, state: &Configuration) -> Score;
+}
+
+impl<E: Evaluator + ?Sized> Evaluator for Box<E> {
+    fn evaluate(&self, state: &Configuration) -> Score {
+        (**self).evaluate(state)
+    }
+}
+
+/// Return (player who just moved, player to move) indices.
+fn players(state: &Configuration) -> (bool, bool) {
+    (!state.current_player, state.current_player)
+}
+
+/// Empty cells given player can reach (by duplicating or jumping).
+fn reachable(state: &Configuration, player: bool) -> Positions {
+    let board = state.board();
+    let blobs = state.blobs(player);
+    let mut cells = blobs
+        .positions()
+        .map(|p| board.neighbours[p as usize])
+        .fold(Positions::default(), |a, b| a.union_with(b));
+    for position in blobs.positions() {
+        for &target in &board.individual_neighbours[1][position as usize] {
+            cells.add(Positions::single(target));
+        }
+    }
+    cells.intersection_with(state.empty_positions())
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+/// Material difference : number of blobs we have more than the opponent.
+pub struct Material;
+
+impl fmt::Display for Material {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "material")
+    }
+}
+
+impl Evaluator for Material {
+    fn evaluate(&self, state: &Configuration) -> Score {
+        state.value().into()
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+/// Mobility difference : number of empty cells we can reach more than the opponent.
+pub struct Mobility;
+
+impl fmt::Display for Mobility {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "mobility")
+    }
+}
+
+impl Evaluator for Mobility {
+    fn evaluate(&self, state: &Configuration) -> Score {
+        let (me, him) = players(state);
+        (reachable(state, me).len() - reachable(state, him).len()).into()
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+/// Frontier : how many of our blobs the opponent can convert on his next move (next to a cell
+/// he can reach), compared to how many of his blobs we could convert.
+pub struct Frontier;
+
+impl fmt::Display for Frontier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "frontier")
+    }
+}
+
+/// Number of given player's blobs his opponent could convert.
+fn exposed(state: &Configuration, player: bool) -> i16 {
+    let board = state.board();
+    reachable(state, !player)
+        .positions()
+        .map(|p| board.neighbours[p as usize])
+        .fold(Positions::default(), |a, b| a.union_with(b))
+        .intersection_with(state.blobs(player))
+        .len()
+}
+
+impl Evaluator for Frontier {
+    fn evaluate(&self, state: &Configuration) -> Score {
+        let (me, him) = players(state);
+        (exposed(state, him) - exposed(state, me)).into()
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+/// Safety : blobs next to holes (or to the board's edge) have fewer neighbours and can be
+/// surrounded less easily. Count how many of our blobs are in such places compared to the
+/// opponent.
+pub struct Safety;
+
+impl fmt::Display for Safety {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "safety")
+    }
+}
+
+/// Number of given player's blobs having less than 8 usable neighbours.
+fn sheltered(state: &Configuration, player: bool) -> i16 {
+    let board = state.board();
+    state
+        .blobs(player)
+        .positions()
+        .filter(|&p| board.neighbours[p as usize].len() < 8)
+        .count() as i16
+}
+
+impl Evaluator for Safety {
+    fn evaluate(&self, state: &Configuration) -> Score {
+        let (me, him) = players(state);
+        (sheltered(state, me) - sheltered(state, him)).into()
+    }
+}
+
+#[derive(Default)]
+/// Weighted sum of other evaluators (saturating below decisive scores).
+pub struct Weighted {
+    terms: Vec<(i32, Box<dyn Evaluator + Send + Sync>)>,
+}
+
+impl Weighted {
+    /// Add given evaluator with given weight.
+    pub fn with<E>(mut self, weight: i32, evaluator: E) -> Self
+    where
+        E: Evaluator + Send + Sync + 'static,
+    {
+        self.terms.push((weight, Box::new(evaluator)));
+        self
+    }
+}
+
+impl fmt::Display for Weighted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut terms = self.terms.iter();
+        if let Some((weight, evaluator)) = terms.next() {
+            write!(f, "{}*{}", weight, evaluator)?;
+        }
+        for (weight, evaluator) in terms {
+            write!(f, " + {}*{}", weight, evaluator)?;
+        }
+        Ok(())
+    }
+}
+
+impl Evaluator for Weighted {
+    fn evaluate(&self, state: &Configuration) -> Score {
+        let sum: i32 = self
+            .terms
+            .iter()
+            .map(|(weight, evaluator)| weight * i32::from(evaluator.evaluate(state).value()))
+            .sum();
+        Score::heuristic(sum)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+    use crate::fixtures::last_blue_blob;
+
+    #[test]
+    fn symmetric_start_is_even() {
+        let board = Board::default();
+        let state = Configuration::new(&board);
+        let evaluator = Weighted::default()
+            .with(3, Material)
+            .with(1, Mobility)
+            .with(2, Frontier)
+            .with(1, Safety);
+        assert_eq!(evaluator.evaluate(&state), Score::DRAW);
+        assert_eq!(Safety.evaluate(&state), Score::DRAW);
+        assert_eq!(Mobility.evaluate(&state), Score::DRAW);
+    }
+
+    #[test]
+    fn evaluations_follow_the_player_who_moved() {
+        let board = Board::default();
+        // red to move, with one more blob than blue
+        let state = last_blue_blob(&board);
+        let evaluators: Vec<Box<dyn Evaluator>> = vec![
+            Box::new(Material),
+            Box::new(Mobility),
+            Box::new(Frontier),
+            Box::new(Safety),
+        ];
+        for evaluator in &evaluators {
+            assert_eq!(
+                evaluator.evaluate(&state),
+                -evaluator.evaluate(&state.skip_play())
+            );
+        }
+        assert_eq!(Material.evaluate(&state), Score::from(-1));
+        let wide = Weighted::default().with(100, Material).with(100, Material);
+        assert_eq!(wide.evaluate(&state).value(), -200);
+        let saturated = Weighted::default().with(i32::MAX / 2, Material);
+        assert!(!saturated.evaluate(&state).is_decisive());
+    }
+}
@@ -0,0 +1,21 @@
+//! Positions shared by the tests of several modules.
+use crate::board::Board;
+use crate::configuration::Configuration;
+use crate::positions::Positions;
+
+/// Red to move on the first row of given board with two blobs (cells 0 and 1) against blue's
+/// single blob (cell 3) : red can convert the last blue blob right now.
+pub(crate) fn last_blue_blob(board: &Board) -> Configuration<'_> {
+    Configuration::deserialize(&format!("0rr b{}", " ".repeat(60)), board).unwrap()
+}
+
+/// Board where only cells 0, 1 and 3 are playable.
+pub(crate) fn three_cells_board() -> Board {
+    Board::new(Positions::from_cells(&[0, 1, 3]).invert())
+}
+
+/// Red (cell 0) to move against blue (cell 1) on given board : on `three_cells_board` red has
+/// to pass, blue has to jump away and red then fills the board.
+pub(crate) fn red_stuck(board: &Board) -> Configuration<'_> {
+    Configuration::deserialize(&format!("0rb{}", " ".repeat(62)), board).unwrap()
+}
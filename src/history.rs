@@ -0,0 +1,105 @@
+//! Provide a `History` of played moves, allowing to take them back.
+use super::configuration::{Configuration, Movement, Undo};
+
+#[derive(Default, Debug, Clone)]
+/// Stack of moves played on a `Configuration` (`None` for skips) with their `Undo` records.
+pub struct History {
+    played: Vec<(Option<Movement>, Undo)>,
+}
+
+impl History {
+    /// Play given move (skip turn if `None`) on given `Configuration` and remember it.
+    pub fn play(&mut self, state: &mut Configuration, movement: Option<Movement>) {
+        let undo = match movement {
+            Some(ref movement) => state.apply_movement(movement),
+            None => state.apply_skip(),
+        };
+        self.played.push((movement, undo));
+    }
+
+    /// Take back last move on given `Configuration` (which must be the one we played on).
+    /// Return the move taken back (`None` for a skip) or nothing if no move was played.
+    pub fn take_back(&mut self, state: &mut Configuration) -> Option<Option<Movement>> {
+        self.played.pop().map(|(movement, undo)| {
+            state.undo_movement(&undo);
+            movement
+        })
+    }
+
+    /// Take back moves until given player's last move is taken back, so that the player can
+    /// play it again.
+    /// Return false (and take back nothing) if the player did not play yet.
+    pub fn take_back_turn(&mut self, state: &mut Configuration, player: bool) -> bool {
+        if !self.played.iter().any(|(_, undo)| undo.player() == player) {
+            return false;
+        }
+        while let Some((_, undo)) = self.played.pop() {
+            state.undo_movement(&undo);
+            if undo.player() == player {
+                break;
+            }
+        }
+        true
+    }
+
+    /// Iterate on played moves, from first to last (`None` for skips).
+    pub fn moves(&self) -> impl Iterator<Item = Option<Movement>> + '_ {
+        self.played.iter().map(|(movement, _)| *movement)
+    }
+
+    /// Number of moves played (skips included).
+    pub fn len(&self) -> usize {
+        self.played.len()
+    }
+
+    /// Did nobody play yet ?
+    pub fn is_empty(&self) -> bool {
+        self.played.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+
+    #[test]
+    fn taking_back_restores_configurations() {
+        let board = Board::default();
+        let start = Configuration::new(&board);
+        let mut state = start;
+        let mut history = History::default();
+        let mut states = Vec::new();
+        for turn in 0..30 {
+            states.push(state);
+            let movement = state.movements().nth(turn * 7 % 13);
+            history.play(&mut state, movement);
+        }
+        while let Some(state_before) = states.pop() {
+            history.take_back(&mut state);
+            assert_eq!(state.serialize(), state_before.serialize());
+            assert_eq!(state.current_player, state_before.current_player);
+            assert_eq!(state.hash(), state_before.hash());
+        }
+        assert!(history.is_empty());
+        assert_eq!(history.take_back(&mut state), None);
+    }
+
+    #[test]
+    fn turns_are_taken_back_for_given_player() {
+        let board = Board::default();
+        let start = Configuration::new(&board);
+        let mut state = start;
+        let mut history = History::default();
+        // blue cannot take back anything yet
+        history.play(&mut state, Some(Movement::Duplicate(13)));
+        assert!(!history.take_back_turn(&mut state, true));
+        assert_eq!(history.len(), 1);
+        // red takes back its move and blue's answer (a skip)
+        history.play(&mut state, None);
+        assert!(history.take_back_turn(&mut state, false));
+        assert!(history.is_empty());
+        assert_eq!(state.hash(), start.hash());
+        assert!(!state.current_player);
+    }
+}
@@ -3,10 +3,19 @@
 #![warn(clippy::all)]
 
 pub mod board;
+pub mod clock;
 pub mod configuration;
+pub mod evaluation;
+#[cfg(test)]
+pub(crate) mod fixtures;
+pub mod history;
 pub(crate) mod positions;
+pub mod protocol;
+pub mod record;
+pub mod score;
 pub(crate) mod shmem;
 pub mod strategy;
+pub(crate) mod zobrist;
 
 #[cfg(test)]
 mod tests {
@@ -1,20 +1,19 @@
 extern crate blobwar;
-use blobwar::board::Board;
 use blobwar::configuration::Configuration;
-use blobwar::strategy::{Greedy, Human, MinMax, AlphaBeta};
-use blobwar::strategy::{alpha_beta_anytime, min_max_anytime};
+use blobwar::evaluation::Material;
+use blobwar::strategy::{AlphaBeta, MinMax};
 
 fn main() {
     // let board = Board::load("constrained").expect("failed loading board");
     let board = Default::default();
     let mut game = Configuration::new(&board);
-    // game.battle(MinMax(2), Greedy()); // red, blue
-    // game.battle(Greedy(), MinMax(2)); // red, blue
-    // game.battle(MinMax(3), MinMax(2)); // red, blue
-    game.battle(MinMax(3), AlphaBeta(4)); // red, blue
-    // game.battle(AlphaBeta(4), MinMax(3)); // red, blue
+    // game.battle(MinMax(2, Material), Greedy(Material)); // red, blue
+    // game.battle(Greedy(Material), MinMax(2, Material)); // red, blue
+    // game.battle(MinMax(3, Material), MinMax(2, Material)); // red, blue
+    game.battle(MinMax(3, Material), AlphaBeta(4, Material)); // red, blue
+    // game.battle(AlphaBeta(4, Material), MinMax(3, Material)); // red, blue
     // alpha_beta_anytime(&game);
-    // game.battle(alpha_beta_any_time(), MinMax(3)); // red, blue
-    // game.battle(MinMax(3), MinMax(4)); // red, blue
-    // game.battle(MinMax(4), MinMax(3)); // red, blue
+    // game.battle(alpha_beta_any_time(), MinMax(3, Material)); // red, blue
+    // game.battle(MinMax(3, Material), MinMax(4, Material)); // red, blue
+    // game.battle(MinMax(4, Material), MinMax(3, Material)); // red, blue
 }
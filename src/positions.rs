@@ -1,11 +1,21 @@
-//! a `Positions` is a set of 64 bits locating something on the board.
+//! a `Positions` is a set of bits locating something on the board.
 //! we use it to keep track of blue blobs, red blobs and holes.
-use std;
+//!
+//! Boards can have up to `MAX_SIDE` rows and columns. Whatever the board size, cells are
+//! indexed in a `MAX_SIDE` x `MAX_SIDE` grid so that coordinates conversions do not depend on
+//! the board : cells outside of the board are simply holes.
 use std::fmt;
-use std::iter::repeat;
-use std::ops::Deref;
 
-/// Coordinate of a board cell (between 0 and 64).
+/// Maximal number of rows (and columns) of a board.
+pub const MAX_SIDE: u8 = 12;
+
+/// Number of cells of the grid all boards are stored in.
+pub const CELLS: usize = MAX_SIDE as usize * MAX_SIDE as usize;
+
+/// Number of 64 bits words needed to store a bit for each cell.
+const WORDS: usize = CELLS.div_ceil(64);
+
+/// Coordinate of a board cell (between 0 and `CELLS`).
 pub type Position = u8;
 
 pub trait BoardPosition {
@@ -19,10 +29,10 @@ pub trait BoardPosition {
 
 impl BoardPosition for Position {
     fn from_2d(x: u8, y: u8) -> Self {
-        y * 8 + x
+        y * MAX_SIDE + x
     }
     fn to_2d(self) -> (u8, u8) {
-        (self % 8, self / 8)
+        (self % MAX_SIDE, self / MAX_SIDE)
     }
     fn distance_to(self, other: Self) -> u8 {
         let (x1, y1) = self.to_2d();
@@ -31,100 +41,99 @@ impl BoardPosition for Position {
     }
 }
 
-#[derive(Copy, Clone)]
-/// Set of `Position` as a bitfield (position 0 is bit of lowest weight)
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq, Hash)]
+/// Set of `Position` as a bitfield (position 0 is bit of lowest weight of the first word)
 /// This allows to store any combination of board cells in a very compact manner.
 /// Moreover we can then use bit masking operations to compute intersections and unions...
-pub struct Positions(pub u64);
-
-impl Deref for Positions {
-    type Target = u64;
-    fn deref(&self) -> &u64 {
-        &self.0
-    }
-}
+/// Bits after the last cell are always 0.
+pub struct Positions(pub [u64; WORDS]);
 
 impl Positions {
-    /// Invert all positions we contain.
-    pub fn invert(&self) -> Self {
-        Positions(!self.0)
+    /// Set of all cells of the grid.
+    pub fn all() -> Self {
+        let mut words = [!0; WORDS];
+        if !CELLS.is_multiple_of(64) {
+            words[WORDS - 1] = (1 << (CELLS % 64)) - 1;
+        }
+        Positions(words)
     }
-    /// Iterate on bits from lowest to highest.
-    /// Will stop as soon as all remaining bits are set to 0.
-    pub fn bits(&self) -> BitIterator {
-        BitIterator { remaining: self.0 }
+    /// Set of given cells.
+    pub fn from_cells(cells: &[Position]) -> Self {
+        cells
+            .iter()
+            .fold(Positions::default(), |set, &cell| set.union_with(Positions::single(cell)))
     }
-    /// Iterate on all our 64 bits.
-    pub fn full_bits(&self) -> impl Iterator<Item = bool> {
-        self.bits().chain(repeat(false)).take(64)
+    /// Invert all positions we contain.
+    pub fn invert(&self) -> Self {
+        let mut inverted = Positions::all();
+        inverted.remove(*self);
+        inverted
     }
     /// Do we have something on given `Position` ?
     pub fn contains(&self, position: Position) -> bool {
-        !self
-            .intersection_with(Positions::single(position))
-            .is_empty()
+        self.0[position as usize / 64] & (1 << (position % 64)) != 0
     }
     /// Iterate on all `Position` inside us.
     pub fn positions(&self) -> impl Iterator<Item = Position> {
-        self.bits()
-            .enumerate()
-            .filter(|&(_, bit)| bit)
-            .map(|(position, _)| position as Position)
+        let words = self.0;
+        (0..WORDS).flat_map(move |word| {
+            let mut remaining = words[word];
+            std::iter::from_fn(move || {
+                if remaining == 0 {
+                    None
+                } else {
+                    let bit = remaining.trailing_zeros();
+                    remaining &= remaining - 1;
+                    Some((word * 64) as Position + bit as Position)
+                }
+            })
+        })
     }
     /// Do we contain nothing ?
     pub fn is_empty(&self) -> bool {
-        self.eq(&0)
+        self.0.iter().all(|&word| word == 0)
     }
     /// How many bits are set ?
-    pub fn len(&self) -> i8 {
-        self.count_ones() as i8
+    pub fn len(&self) -> i16 {
+        self.0.iter().map(|word| word.count_ones() as i16).sum()
     }
     /// Return positions obtained when intersecting with given ones.
     pub fn intersection_with(&self, other: Positions) -> Positions {
-        Positions(self.0 & other.0)
+        let mut words = self.0;
+        for (word, other) in words.iter_mut().zip(other.0) {
+            *word &= other;
+        }
+        Positions(words)
     }
     /// Return positions obtained when taking union with given ones.
     pub fn union_with(&self, other: Positions) -> Positions {
-        Positions(self.0 | other.0)
+        let mut words = self.0;
+        for (word, other) in words.iter_mut().zip(other.0) {
+            *word |= other;
+        }
+        Positions(words)
     }
     /// Remove given `Positions` from us.
     pub fn remove(&mut self, to_remove: Positions) {
-        self.0 &= !to_remove.0
+        for (word, other) in self.0.iter_mut().zip(to_remove.0) {
+            *word &= !other;
+        }
     }
     /// Add given `Positions` inside us.
     pub fn add(&mut self, to_remove: Positions) {
-        self.0 |= to_remove.0
+        for (word, other) in self.0.iter_mut().zip(to_remove.0) {
+            *word |= other;
+        }
     }
     /// `Positions` initialized with only given `Position` inside.
     pub fn single(position: Position) -> Self {
-        Positions(1u64 << position)
+        let mut words = [0; WORDS];
+        words[position as usize / 64] = 1 << (position % 64);
+        Positions(words)
     }
     /// Do we contain every possible `Position` ?
     pub fn is_all(&self) -> bool {
-        self.0 == std::u64::MAX
-    }
-}
-
-impl Default for Positions {
-    fn default() -> Self {
-        Positions(0)
-    }
-}
-
-pub struct BitIterator {
-    remaining: u64,
-}
-
-impl Iterator for BitIterator {
-    type Item = bool;
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.remaining == 0 {
-            None
-        } else {
-            let bit = self.remaining & 1;
-            self.remaining >>= 1;
-            Some(bit == 1)
-        }
+        *self == Positions::all()
     }
 }
 
@@ -142,3 +151,24 @@ impl fmt::Display for Positions {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sets_span_several_words() {
+        let last = (CELLS - 1) as Position;
+        let set = Positions::from_cells(&[0, 63, 64, 130, last]);
+        assert_eq!(set.len(), 5);
+        assert_eq!(set.positions().collect::<Vec<_>>(), vec![0, 63, 64, 130, last]);
+        assert!(set.contains(64) && !set.contains(65));
+        let inverted = set.invert();
+        assert_eq!(inverted.len(), CELLS as i16 - 5);
+        assert!(inverted.union_with(set).is_all());
+        assert!(inverted.intersection_with(set).is_empty());
+        assert_eq!(Position::from_2d(11, 11), last);
+        assert_eq!(last.to_2d(), (11, 11));
+        assert_eq!(Position::from_2d(3, 4).distance_to(Position::from_2d(5, 3)), 2);
+    }
+}
@@ -0,0 +1,164 @@
+//! Messages exchanged between the server and network clients.
+//!
+//! Each message is a json object on its own line, its kind given by the `type` field.
+//! A game goes as follows :
+//! - the server sends `hello` with its protocol version and the client answers `hello` ;
+//! - the server sends `colour` before the first position of each game ;
+//! - on each turn of the client the server sends `position` and the client answers `move` or
+//!   `pass` within the given time. Illegal answers are reported with `illegal_move` ; they
+//!   lose the game, like late answers ;
+//! - the server sends `result` at the end of each game (several games can be played on the
+//!   same connection) then `bye` before closing the connection.
+//!
+//! Legacy servers and clients have no messages : the server sends a bare serialized
+//! `Configuration` and the client answers with a json `Option<Movement>`.
+use std::io::{self, BufRead, Write};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::configuration::Movement;
+use crate::record::Forfeit;
+
+/// Version of the protocol we speak.
+pub const VERSION: u32 = 1;
+
+/// Store moves in algebraic notation.
+mod notation {
+    use crate::configuration::Movement;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(movement: &Movement, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(movement)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Movement, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+/// A message of the protocol, in either direction.
+pub enum Message {
+    /// First message of both sides.
+    Hello {
+        /// Protocol version of the sender.
+        version: u32,
+        /// Name of the sender.
+        name: String,
+    },
+    /// Server tells the client which player it is.
+    Colour {
+        /// `false` for red, `true` for blue.
+        player: bool,
+    },
+    /// Server asks the client to play.
+    Position {
+        /// Serialized `Configuration` to play on (holes included).
+        configuration: String,
+        /// Time available for the move in milliseconds, if limited.
+        millis: Option<u64>,
+    },
+    /// Client plays a move.
+    Move {
+        /// The move in algebraic notation.
+        #[serde(with = "notation")]
+        movement: Movement,
+    },
+    /// Client passes its turn.
+    Pass,
+    /// Server rejects the last answer of the client (which loses the game).
+    IllegalMove {
+        /// Why the answer is rejected.
+        reason: String,
+    },
+    /// Server announces final blob counts.
+    Result {
+        /// Number of red blobs.
+        red: i16,
+        /// Number of blue blobs.
+        blue: i16,
+        /// Set if a player lost by forfeit.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        forfeit: Option<Forfeit>,
+    },
+    /// Last message before closing the connection.
+    Bye,
+}
+
+impl Message {
+    /// Answer for given move (`None` to pass).
+    pub fn play(movement: Option<Movement>) -> Self {
+        match movement {
+            Some(movement) => Message::Move { movement },
+            None => Message::Pass,
+        }
+    }
+
+    /// Write the message on its own line.
+    pub fn send<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut line = serde_json::to_string(self)?;
+        line.push('\n');
+        writer.write_all(line.as_bytes())
+    }
+
+    /// Read next message. The end of the stream is an `UnexpectedEof` error.
+    pub fn receive<R: BufRead>(reader: &mut R) -> io::Result<Self> {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(serde_json::from_str(&line)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_are_self_describing_lines() {
+        let messages = [
+            Message::Hello {
+                version: VERSION,
+                name: "client".to_owned(),
+            },
+            Message::Colour { player: true },
+            Message::Position {
+                configuration: "0r      b/        ".to_owned(),
+                millis: Some(1000),
+            },
+            Message::play(Some(Movement::Jump(0, 26))),
+            Message::play(None),
+            Message::IllegalMove {
+                reason: "no blob to jump from".to_owned(),
+            },
+            Message::Result {
+                red: 3,
+                blue: 1,
+                forfeit: None,
+            },
+            Message::Bye,
+        ];
+        let mut stream = Vec::new();
+        for message in &messages {
+            message.send(&mut stream).unwrap();
+        }
+        let text = String::from_utf8(stream.clone()).unwrap();
+        assert_eq!(text.lines().count(), messages.len());
+        assert!(text.contains(r#"{"type":"move","movement":"a1-c3"}"#));
+        let mut reader = &stream[..];
+        for message in &messages {
+            assert_eq!(&Message::receive(&mut reader).unwrap(), message);
+        }
+        assert_eq!(
+            Message::receive(&mut reader).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+        let mut invalid = &b"{\"type\":\"move\",\"movement\":\"z9\"}\n"[..];
+        assert!(Message::receive(&mut invalid).is_err());
+    }
+}
@@ -0,0 +1,354 @@
+//! Provide `GameRecord`s : what happened during a game, to save and replay it.
+//! Records are stored as json files.
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+use serde_derive::{Deserialize, Serialize};
+
+use super::board::{Board, DeserializeError};
+use super::configuration::{Configuration, Movement};
+
+/// Store moves in algebraic notation (`pass` for skipped turns).
+mod notation {
+    use crate::configuration::Movement;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        movement: &Option<Movement>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match movement {
+            Some(movement) => serializer.collect_str(movement),
+            None => serializer.serialize_str("pass"),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Movement>, D::Error> {
+        let movement = String::deserialize(deserializer)?;
+        if movement == "pass" {
+            Ok(None)
+        } else {
+            movement.parse().map(Some).map_err(D::Error::custom)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+/// A move as played during a game.
+pub struct RecordedMove {
+    /// What was played (`None` for a skipped turn), stored in algebraic notation.
+    #[serde(with = "notation")]
+    pub movement: Option<Movement>,
+    /// How long the player took to decide, in milliseconds.
+    pub millis: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+/// Why a player lost before the end of the game.
+pub enum ForfeitReason {
+    /// It played an illegal move (or sent something which is not a move).
+    IllegalMove,
+    /// It ran out of time.
+    Timeout,
+    /// Its connection was lost.
+    Disconnected,
+}
+
+impl fmt::Display for ForfeitReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ForfeitReason::IllegalMove => write!(f, "illegal move"),
+            ForfeitReason::Timeout => write!(f, "out of time"),
+            ForfeitReason::Disconnected => write!(f, "disconnected"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+/// A player lost the game by forfeit.
+pub struct Forfeit {
+    /// Who forfeited (`false` for red, `true` for blue).
+    pub player: bool,
+    /// Why.
+    pub reason: ForfeitReason,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+/// Final blob counts.
+pub struct GameResult {
+    /// Number of red blobs.
+    pub red: i16,
+    /// Number of blue blobs.
+    pub blue: i16,
+    /// Set if the game was lost by forfeit, whatever the blob counts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forfeit: Option<Forfeit>,
+}
+
+impl GameResult {
+    /// Who won (`false` for red, `true` for blue) ; `None` on a draw.
+    pub fn winner(&self) -> Option<bool> {
+        if let Some(forfeit) = self.forfeit {
+            return Some(!forfeit.player);
+        }
+        match self.red - self.blue {
+            x if x > 0 => Some(false),
+            x if x < 0 => Some(true),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+/// Everything needed to replay a game.
+pub struct GameRecord {
+    /// Name of the board, if known.
+    pub board: Option<String>,
+    /// Serialized start `Configuration` (holes included).
+    pub start: String,
+    /// Maximal number of plies of the game (see `Rules`), if any.
+    #[serde(default)]
+    pub max_plies: Option<u32>,
+    /// Names of red and blue players.
+    pub players: [String; 2],
+    /// All moves in order.
+    pub moves: Vec<RecordedMove>,
+    /// Final blob counts (`None` if the game was not finished).
+    pub result: Option<GameResult>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// Why a record does not replay.
+pub enum ReplayError {
+    /// Move at given index is not allowed in the configuration it is played on.
+    IllegalMove(usize, Movement),
+    /// Player passes at given index although it can move.
+    IllegalPass(usize),
+    /// Move at given index is played after the end of the game (or the maximal number of
+    /// plies).
+    MoveAfterEnd(usize),
+    /// The recorded result is not the one obtained when replaying.
+    WrongResult(GameResult),
+    /// The start configuration cannot be deserialized.
+    InvalidStart(DeserializeError),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReplayError::IllegalMove(index, movement) => {
+                write!(f, "illegal move {} at index {}", movement, index)
+            }
+            ReplayError::IllegalPass(index) => {
+                write!(f, "illegal pass at index {} (moves are possible)", index)
+            }
+            ReplayError::MoveAfterEnd(index) => {
+                write!(
+                    f,
+                    "move at index {} is played after the end of the game",
+                    index
+                )
+            }
+            ReplayError::WrongResult(result) => write!(
+                f,
+                "game ends with {} red and {} blue blobs instead of the recorded result",
+                result.red, result.blue
+            ),
+            ReplayError::InvalidStart(error) => write!(f, "invalid start configuration: {}", error),
+        }
+    }
+}
+
+impl Error for ReplayError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ReplayError::InvalidStart(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<DeserializeError> for ReplayError {
+    fn from(error: DeserializeError) -> Self {
+        ReplayError::InvalidStart(error)
+    }
+}
+
+impl GameRecord {
+    /// The `Board` the game was played on.
+    pub fn board(&self) -> Result<Board, DeserializeError> {
+        Board::deserialize(&self.start)
+    }
+
+    /// The start `Configuration` on given `Board` (obtained with `board`).
+    pub fn start<'a>(&self, board: &'a Board) -> Result<Configuration<'a>, DeserializeError> {
+        Configuration::deserialize(&self.start, board)
+    }
+
+    /// Iterate on configurations reached after each move, checking moves on the way.
+    /// Iteration ends after the first illegal move.
+    pub fn replay<'r, 'a>(&'r self, board: &'a Board) -> Result<Replay<'r, 'a>, DeserializeError> {
+        Ok(Replay {
+            state: self.start(board)?,
+            max_plies: self.max_plies,
+            moves: self.moves.iter().enumerate(),
+            failed: false,
+        })
+    }
+
+    /// Replay the whole game and check the recorded result.
+    pub fn validate(&self) -> Result<(), ReplayError> {
+        let board = self.board()?;
+        let mut state = self.start(&board)?;
+        for step in self.replay(&board)? {
+            state = step?;
+        }
+        if let Some(result) = self.result {
+            let replayed = GameResult {
+                red: state.blobs(false).len(),
+                blue: state.blobs(true).len(),
+                forfeit: result.forfeit,
+            };
+            let capped = self
+                .max_plies
+                .is_some_and(|max_plies| self.moves.len() as u32 >= max_plies);
+            let ended = state.game_over() || capped || result.forfeit.is_some();
+            if replayed != result || !ended {
+                return Err(ReplayError::WrongResult(replayed));
+            }
+        }
+        Ok(())
+    }
+
+    /// Load record from given json file.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+    }
+
+    /// Save record into given json file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), self)?;
+        Ok(())
+    }
+}
+
+/// Iterator on configurations of a replayed game.
+pub struct Replay<'r, 'a> {
+    state: Configuration<'a>,
+    max_plies: Option<u32>,
+    moves: std::iter::Enumerate<std::slice::Iter<'r, RecordedMove>>,
+    failed: bool,
+}
+
+impl<'a> Iterator for Replay<'_, 'a> {
+    type Item = Result<Configuration<'a>, ReplayError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let (index, recorded) = self.moves.next()?;
+        let step = if self.state.game_over() || self.max_plies.is_some_and(|m| index as u32 >= m) {
+            Err(ReplayError::MoveAfterEnd(index))
+        } else {
+            match recorded.movement {
+                Some(movement) if !self.state.check_move(&movement) => {
+                    Err(ReplayError::IllegalMove(index, movement))
+                }
+                Some(movement) => {
+                    self.state.apply_movement(&movement);
+                    Ok(self.state)
+                }
+                None if self.state.can_move() => Err(ReplayError::IllegalPass(index)),
+                None => {
+                    self.state.apply_skip();
+                    Ok(self.state)
+                }
+            }
+        };
+        self.failed = step.is_err();
+        Some(step)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluation::Material;
+    use crate::positions::Positions;
+    use crate::strategy::{AlphaBeta, Greedy};
+
+    /// A full game on the two first rows.
+    fn game() -> GameRecord {
+        let board = Board::with_size(8, 2, Positions::default());
+        let mut state = Configuration::deserialize("0r      b/        ", &board).unwrap();
+        state.battle(Greedy(Material), AlphaBeta(2, Material))
+    }
+
+    #[test]
+    fn battles_are_recorded_and_replayed() {
+        let record = game();
+        assert_eq!(record.players[0], "Greedy (material)");
+        assert!(record.result.is_some());
+        assert_eq!(record.validate(), Ok(()));
+        let path = std::env::temp_dir().join(format!("blobwar-game-{}.json", std::process::id()));
+        record.save(&path).unwrap();
+        let loaded = GameRecord::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, record);
+        let json = serde_json::to_string(&record.moves[0]).unwrap();
+        assert!(json.starts_with(&format!(
+            r#"{{"movement":"{}""#,
+            record.moves[0].movement.unwrap()
+        )));
+    }
+
+    #[test]
+    fn games_end_after_maximal_number_of_plies() {
+        let mut board = Board::default();
+        board.rules.max_plies = Some(6);
+        let record = Configuration::new(&board).battle(Greedy(Material), Greedy(Material));
+        assert_eq!(record.moves.len(), 6);
+        assert_eq!(record.validate(), Ok(()));
+    }
+
+    #[test]
+    fn illegal_moves_are_detected() {
+        let mut record = game();
+        // red duplicating far from its blob
+        record.moves[0].movement = Some(Movement::Duplicate(5));
+        let board = record.board().unwrap();
+        let steps: Vec<_> = record.replay(&board).unwrap().collect();
+        assert_eq!(steps.len(), 1);
+        assert_eq!(
+            record.validate(),
+            Err(ReplayError::IllegalMove(0, Movement::Duplicate(5)))
+        );
+        record.moves[0].movement = None;
+        assert_eq!(record.validate(), Err(ReplayError::IllegalPass(0)));
+        let mut record = game();
+        record.moves.push(RecordedMove {
+            movement: None,
+            millis: 0,
+        });
+        assert_eq!(
+            record.validate(),
+            Err(ReplayError::MoveAfterEnd(record.moves.len() - 1))
+        );
+        record.start.push('h');
+        assert_eq!(
+            record.validate(),
+            Err(ReplayError::InvalidStart(DeserializeError::RowLength {
+                row: 1,
+                expected: 8,
+                found: 9
+            }))
+        );
+    }
+}
@@ -0,0 +1,185 @@
+//! Search scores.
+//! A `Score` is either an heuristic value (given by an `Evaluator`) or a decisive one : the game
+//! ends with a win or a loss in a known number of plies. Decisive scores dominate all heuristic
+//! ones and quicker wins (slower losses) are better. Draws are scored exactly zero.
+//!
+//! Like evaluations, scores are given from the point of view of one player : negating a score
+//! gives the opponent's point of view.
+use super::configuration::Configuration;
+use std::fmt;
+use std::ops::{Add, Neg, Sub};
+
+/// Score of a won game (reached right now).
+const WIN: i16 = 30_000;
+
+/// Maximal number of plies before the end of a game for decisive scores.
+const MAX_PLIES: i16 = 1_000;
+
+/// Heuristic scores are strictly within (-LIMIT, LIMIT).
+const LIMIT: i16 = WIN - MAX_PLIES;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// Value of a `Configuration` for a given player.
+pub struct Score(i16);
+
+impl Score {
+    /// Lower than any real score (the initial alpha of a search).
+    pub const MIN: Score = Score(-i16::MAX);
+    /// Higher than any real score (the initial beta of a search).
+    pub const MAX: Score = Score(i16::MAX);
+    /// A drawn game (or an even position).
+    pub const DRAW: Score = Score(0);
+
+    /// Heuristic score, saturated to stay below decisive scores.
+    pub fn heuristic(value: i32) -> Score {
+        Score(value.clamp(i32::from(-LIMIT + 1), i32::from(LIMIT - 1)) as i16)
+    }
+
+    /// Score of a game won in given number of plies.
+    pub fn win_in(plies: usize) -> Score {
+        Score(WIN - plies.min(MAX_PLIES as usize) as i16)
+    }
+
+    /// Score of a game lost in given number of plies.
+    pub fn loss_in(plies: usize) -> Score {
+        -Score::win_in(plies)
+    }
+
+    /// Is it a won or lost game ?
+    pub fn is_decisive(self) -> bool {
+        self.is_win() || self.is_loss()
+    }
+
+    /// Is it a won game ?
+    pub fn is_win(self) -> bool {
+        self.0 >= LIMIT && self.0 <= WIN
+    }
+
+    /// Is it a lost game ?
+    pub fn is_loss(self) -> bool {
+        self.0 <= -LIMIT && self.0 >= -WIN
+    }
+
+    /// In how many plies does the game end (for decisive scores).
+    pub fn plies(self) -> Option<usize> {
+        if self.is_decisive() {
+            Some((WIN - self.0.abs()) as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Score with given raw value (as returned by `value`).
+    pub(crate) fn from_value(value: i16) -> Score {
+        Score(value)
+    }
+
+    /// Raw value (for heuristic scores this is the evaluation).
+    pub fn value(self) -> i16 {
+        self.0
+    }
+
+    /// Score of given configuration if the game is over, reached after given number of plies.
+    /// As for `Configuration::value`, it is given from the point of view of the player who just
+    /// moved.
+    pub fn terminal(state: &Configuration, ply: usize) -> Option<Score> {
+        if !state.game_over() {
+            return None;
+        }
+        Some(match state.value() {
+            v if v > 0 => Score::win_in(ply),
+            v if v < 0 => Score::loss_in(ply),
+            _ => Score::DRAW,
+        })
+    }
+
+    /// Convert a score relative to the root of the search into a score relative to the node at
+    /// given ply (for storing in the transposition table).
+    pub(crate) fn to_node(self, ply: usize) -> Score {
+        self.shift(ply as i16)
+    }
+
+    /// Convert a score relative to the node at given ply into a score relative to the root.
+    pub(crate) fn to_root(self, ply: usize) -> Score {
+        self.shift(-(ply as i16))
+    }
+
+    /// Move decisive scores given number of plies closer (if positive) to the end.
+    fn shift(self, plies: i16) -> Score {
+        if self.is_win() {
+            Score((self.0 + plies).min(WIN))
+        } else if self.is_loss() {
+            Score((self.0 - plies).max(-WIN))
+        } else {
+            self
+        }
+    }
+}
+
+impl From<i16> for Score {
+    fn from(value: i16) -> Self {
+        Score(value)
+    }
+}
+
+impl Neg for Score {
+    type Output = Score;
+    fn neg(self) -> Score {
+        Score(-self.0)
+    }
+}
+
+impl Add<i16> for Score {
+    type Output = Score;
+    fn add(self, other: i16) -> Score {
+        Score(self.0.saturating_add(other).max(-i16::MAX))
+    }
+}
+
+impl Sub<i16> for Score {
+    type Output = Score;
+    fn sub(self, other: i16) -> Score {
+        Score(self.0.saturating_sub(other).max(-i16::MAX))
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.plies() {
+            Some(plies) if self.is_win() => write!(f, "win in {}", plies),
+            Some(plies) => write!(f, "loss in {}", plies),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+
+    #[test]
+    fn decisive_scores_order() {
+        assert!(Score::win_in(1) > Score::win_in(3));
+        assert!(Score::win_in(100) > Score::heuristic(i32::MAX));
+        assert!(Score::loss_in(3) > Score::loss_in(1));
+        assert!(Score::loss_in(100) < Score::heuristic(i32::MIN));
+        assert!(Score::MIN < Score::loss_in(0) && Score::win_in(0) < Score::MAX);
+        assert_eq!(-Score::win_in(4), Score::loss_in(4));
+        assert_eq!(Score::win_in(4).plies(), Some(4));
+        assert_eq!(Score::heuristic(12).plies(), None);
+        assert_eq!(Score::win_in(5).to_node(3).to_root(3), Score::win_in(5));
+        assert_eq!(Score::loss_in(5).to_node(3), Score::loss_in(2));
+    }
+
+    #[test]
+    fn wiped_out_side_is_decisive() {
+        let board = Board::default();
+        // red just moved and blue has no blobs left
+        let state = Configuration::deserialize(&format!("1rr{}", " ".repeat(62)), &board).unwrap();
+        assert_eq!(Score::terminal(&state, 3), Some(Score::win_in(3)));
+        let state = state.skip_play();
+        assert_eq!(Score::terminal(&state, 3), Some(Score::loss_in(3)));
+        assert_eq!(Score::terminal(&Configuration::new(&board), 0), None);
+    }
+}
@@ -1,21 +1,35 @@
 //! Provide methods sharing a `Movement` in shared memory between processes.
 //! Slave anytime process writes new moves atomically and master process only reads them.
+//!
+//! Each segment gets its own name, built from the master's pid and a counter, so that several
+//! masters can run on the same machine. The name is given to the slave process.
 use super::configuration::Movement;
 use libc::off_t;
-use libc::{c_void, size_t};
-use nix;
-use nix::fcntl::{O_CREAT, O_RDWR};
+use libc::{c_void, pid_t, size_t};
+use nix::fcntl::{O_CREAT, O_EXCL, O_RDWR};
 use nix::sys::mman::MAP_SHARED;
 use nix::sys::mman::{mmap, munmap};
 use nix::sys::mman::{shm_open, shm_unlink};
 use nix::sys::mman::{PROT_READ, PROT_WRITE};
 use nix::sys::stat::{S_IRUSR, S_IWUSR};
 use nix::unistd::{close, ftruncate};
+use std::fs;
+use std::io;
 use std::os::unix::io::RawFd;
+use std::process;
 use std::ptr;
 
 use std::mem;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// Prefix of all our segment names.
+const PREFIX: &str = "blobwar-";
+
+/// Where shared memory segments are visible as files (on linux).
+const SHM_DIRECTORY: &str = "/dev/shm";
+
+/// Count segments created by this process.
+static SEGMENTS: AtomicU64 = AtomicU64::new(0);
 
 struct InnerAtomicMove {
     movements: [Option<Movement>; 2],
@@ -25,65 +39,62 @@ struct InnerAtomicMove {
 pub struct AtomicMove {
     fd: RawFd,
     created: bool,
+    released: bool,
     address: *mut c_void,
+    name: String,
 }
 
 impl Drop for AtomicMove {
     fn drop(&mut self) {
-        let size = mem::size_of::<InnerAtomicMove>();
-        close(self.fd).unwrap();
-        munmap(self.address, size as size_t).unwrap();
-
-        if self.created {
-            shm_unlink("blobwar").unwrap();
+        if let Err(e) = self.release() {
+            eprintln!("failed releasing shared memory {}: {}", self.name, e);
         }
     }
 }
 
 impl AtomicMove {
+    /// Create a new segment with a name unique to this process and this game.
     pub fn new() -> Result<Self, nix::Error> {
+        let game = SEGMENTS.fetch_add(1, Ordering::Relaxed);
+        let name = format!("/{}{}-{}", PREFIX, process::id(), game);
         let size = mem::size_of::<InnerAtomicMove>();
-        let fd = shm_open("blobwar", O_CREAT | O_RDWR, S_IRUSR | S_IWUSR)?;
-        ftruncate(fd, size as off_t)?;
-
-        let address = mmap(
-            ptr::null_mut(),
-            size as size_t,
-            PROT_READ | PROT_WRITE,
-            MAP_SHARED,
+        let fd = shm_open(name.as_str(), O_CREAT | O_EXCL | O_RDWR, S_IRUSR | S_IWUSR)?;
+        let mut atomic_move = AtomicMove {
             fd,
-            0,
-        )?;
+            address: ptr::null_mut(),
+            created: true,
+            released: false,
+            name,
+        };
+        ftruncate(fd, size as off_t)?;
+        atomic_move.address = map(fd)?;
 
         let atomic: &mut InnerAtomicMove =
-            unsafe { (address as *mut InnerAtomicMove).as_mut().unwrap() };
+            unsafe { (atomic_move.address as *mut InnerAtomicMove).as_mut().unwrap() };
         atomic.movements[0] = None;
         atomic.movements[1] = None;
         atomic.selected = Default::default();
 
-        Ok(AtomicMove {
-            fd,
-            address,
-            created: true,
-        })
+        Ok(atomic_move)
     }
 
-    pub fn connect() -> Result<Self, nix::Error> {
-        let size = mem::size_of::<InnerAtomicMove>();
-        let fd = shm_open("blobwar", O_RDWR, S_IRUSR | S_IWUSR)?;
-        let address = mmap(
-            ptr::null_mut(),
-            size as size_t,
-            PROT_READ | PROT_WRITE,
-            MAP_SHARED,
-            fd,
-            0,
-        )?;
-        Ok(AtomicMove {
+    /// Connect to the segment with given name (created by the master process).
+    pub fn connect(name: &str) -> Result<Self, nix::Error> {
+        let fd = shm_open(name, O_RDWR, S_IRUSR | S_IWUSR)?;
+        let mut atomic_move = AtomicMove {
             fd,
-            address,
+            address: ptr::null_mut(),
             created: false,
-        })
+            released: false,
+            name: name.to_owned(),
+        };
+        atomic_move.address = map(fd)?;
+        Ok(atomic_move)
+    }
+
+    /// Name of the segment, to give to the slave process.
+    pub fn name(&self) -> &str {
+        &self.name
     }
 
     pub fn store(&mut self, movement: Option<Movement>) {
@@ -99,4 +110,129 @@ impl AtomicMove {
             unsafe { (self.address as *const InnerAtomicMove).as_ref().unwrap() };
         atomic.movements[atomic.selected.load(Ordering::SeqCst) % 2]
     }
+
+    /// Release the segment, reporting errors (dropping it releases it silently).
+    pub fn close(mut self) -> Result<(), nix::Error> {
+        self.release()
+    }
+
+    /// Unmap, close and (if we created it) unlink the segment.
+    /// Every step is attempted even if a previous one failed.
+    fn release(&mut self) -> Result<(), nix::Error> {
+        if self.released {
+            return Ok(());
+        }
+        self.released = true;
+        let unmapped = if self.address.is_null() {
+            Ok(())
+        } else {
+            munmap(self.address, mem::size_of::<InnerAtomicMove>() as size_t)
+        };
+        let closed = close(self.fd);
+        let unlinked = if self.created {
+            shm_unlink(self.name.as_str())
+        } else {
+            Ok(())
+        };
+        unmapped.and(closed).and(unlinked)
+    }
+}
+
+fn map(fd: RawFd) -> Result<*mut c_void, nix::Error> {
+    mmap(
+        ptr::null_mut(),
+        mem::size_of::<InnerAtomicMove>() as size_t,
+        PROT_READ | PROT_WRITE,
+        MAP_SHARED,
+        fd,
+        0,
+    )
+}
+
+/// Is a process with given pid still running ?
+fn alive(pid: pid_t) -> bool {
+    let signaled = unsafe { libc::kill(pid, 0) };
+    signaled == 0 || io::Error::last_os_error().raw_os_error() != Some(libc::ESRCH)
+}
+
+/// Remove segments left behind by crashed masters (whose process is gone).
+/// Segments which cannot be removed are reported on stderr and skipped.
+/// Return how many segments were removed.
+pub fn remove_orphan_segments() -> io::Result<usize> {
+    remove_orphans(PREFIX)
+}
+
+/// Remove orphan segments whose name starts with given prefix (at least `PREFIX`).
+fn remove_orphans(prefix: &str) -> io::Result<usize> {
+    let mut removed = 0;
+    for entry in fs::read_dir(SHM_DIRECTORY)? {
+        let file_name = match entry {
+            Ok(entry) => entry.file_name(),
+            Err(e) => {
+                eprintln!("failed listing shared memory segments: {}", e);
+                continue;
+            }
+        };
+        let name = match file_name.to_str() {
+            Some(name) => name,
+            None => continue,
+        };
+        if !name.starts_with(prefix) {
+            continue;
+        }
+        let pid = name
+            .strip_prefix(PREFIX)
+            .and_then(|suffix| suffix.split('-').next())
+            .and_then(|pid| pid.parse::<pid_t>().ok());
+        if let Some(pid) = pid {
+            if !alive(pid) {
+                match shm_unlink(format!("/{}", name).as_str()) {
+                    Ok(()) => removed += 1,
+                    // keep going : other orphans may still be removed
+                    Err(e) => eprintln!("failed removing orphan shared memory {}: {}", name, e),
+                }
+            }
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segments_are_unique_and_released() {
+        let first = AtomicMove::new().unwrap();
+        let mut second = AtomicMove::new().unwrap();
+        assert_ne!(first.name(), second.name());
+        let reader = AtomicMove::connect(second.name()).unwrap();
+        second.store(Some(Movement::Jump(0, 2)));
+        assert_eq!(reader.load(), Some(Movement::Jump(0, 2)));
+        assert_eq!(first.load(), None);
+        let name = second.name().to_owned();
+        reader.close().unwrap();
+        second.close().unwrap();
+        assert!(AtomicMove::connect(&name).is_err());
+    }
+
+    #[test]
+    fn orphans_are_removed_despite_failures() {
+        // pid of a finished process
+        let mut child = process::Command::new("true").spawn().unwrap();
+        let pid = child.id();
+        child.wait().unwrap();
+        let orphan = |suffix: &str| {
+            std::path::Path::new(SHM_DIRECTORY).join(format!("{}{}-{}", PREFIX, pid, suffix))
+        };
+        // a directory cannot be unlinked as a segment
+        let (stuck, orphan) = (orphan("stuck"), orphan("orphan"));
+        fs::create_dir(&stuck).unwrap();
+        fs::write(&orphan, b"").unwrap();
+        // leave segments of other processes alone
+        let removed = remove_orphans(&format!("{}{}-", PREFIX, pid));
+        fs::remove_dir(&stuck).unwrap();
+        assert_eq!(removed.unwrap(), 1);
+        assert!(!orphan.exists());
+    }
 }
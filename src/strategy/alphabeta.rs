@@ -1,76 +1,170 @@
 //! Alpha - Beta algorithm.
 use std::fmt;
 
+use super::context::{deepen, SearchContext};
+use super::endgame;
+use super::tt::{Lookup, TranspositionTable};
 use super::Strategy;
 use crate::configuration::{Configuration, Movement};
+use crate::evaluation::{Evaluator, Material};
+use crate::score::Score;
 use crate::shmem::AtomicMove;
-use rayon::prelude::*;
-use std::collections::HashMap;
-// use std::time::{Duration, Instant};
 
 /// Anytime alpha beta algorithm.
-/// Any time algorithms will compute until a deadline is hit and the process is killed.
-/// They are therefore run in another process and communicate through shared memory.
-/// This function is intended to be called from blobwar_iterative_deepening.
-pub fn alpha_beta_anytime(state: &Configuration) {
-    let mut movement = AtomicMove::connect().expect("failed connecting to shmem");
-    let mut memo: HashMap<String, (i8, Movement)> = HashMap::new();
+/// Any time algorithms compute until a deadline is hit. `IterativeDeepening` normally runs them
+/// on a worker thread which it stops, but can also run them in another process which it kills,
+/// communicating through shared memory.
+/// This function is intended to be called in that process (`blobwar_iterative_deepening`),
+/// with the name of the shared memory segment created by the master process.
+pub fn alpha_beta_anytime(state: &Configuration, segment: &str) {
+    let mut movement = AtomicMove::connect(segment).expect("failed connecting to shmem");
+    let table = TranspositionTable::default();
+    let mut context = SearchContext::default().with_table(&table);
+    alpha_beta_deepening(state, &mut context, |chosen_movement| {
+        movement.store(chosen_movement)
+    });
+}
 
-    // let start_time = Instant::now();
-    for depth in 1..100{
-        let chosen_movement = AlphaBeta(depth).compute_next_move(state, Some(&mut memo));
-        movement.store(chosen_movement);
-        // let end_time = Instant::now();
-        // println!("Time elapsed: {:?}", end_time.duration_since(start_time));
+/// Iterative deepening alpha beta, until the context stops us or up to its depth limit.
+/// The transposition table (if any) is shared between iterations : each one starts by searching
+/// the best move of the previous one.
+/// `report` is called with the best move of each completed iteration.
+/// Endgames are solved instead (see `endgame`).
+pub(crate) fn alpha_beta_deepening<R: FnMut(Option<Movement>)>(
+    state: &Configuration,
+    context: &mut SearchContext,
+    mut report: R,
+) -> Option<Movement> {
+    if let Some(movement) = solve_endgame(state, context) {
+        report(movement);
+        return movement;
     }
+    deepen(
+        state,
+        context,
+        context.depth(100),
+        |depth, context| alpha_beta(depth, 0, state, Score::MIN, Score::MAX, &Material, context).1,
+        report,
+    )
 }
 
-/// Alpha - Beta algorithm with given maximum number of recursions.
-pub struct AlphaBeta(pub u8);
+/// Solve given configuration if few enough empty cells remain.
+/// Return the move to play (`None` for a pass) or `None` if we need to search, which is also
+/// the case when the solution is not exact : it scored some lines on the blob difference at the
+/// horizon, without even the evaluation's care for mobility.
+fn solve_endgame(state: &Configuration, context: &mut SearchContext) -> Option<Option<Movement>> {
+    if !endgame::applies(state) {
+        return None;
+    }
+    endgame::solve(state, context)
+        .filter(|solution| solution.exact)
+        .map(|solution| solution.movement)
+}
 
-impl fmt::Display for AlphaBeta {
+/// Alpha - Beta algorithm with given maximum number of recursions, evaluating leaves with given
+/// `Evaluator`.
+/// When few empty cells remain the endgame solver plays instead.
+pub struct AlphaBeta<E = Material>(pub u8, pub E);
+
+impl<E: Evaluator> fmt::Display for AlphaBeta<E> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Alpha - Beta (max level: {})", self.0)
+        write!(f, "Alpha - Beta (max level: {}, {})", self.0, self.1)
     }
 }
 
-fn alpha_beta(depth: u8, state: &Configuration, mut alpha: i8, beta: i8) -> (i8, Option<Movement>) {
-    if depth == 0 || !state.can_move() {
-        return (state.value(), None);
+/// Alpha - Beta algorithm, using the context's transposition table if any.
+/// Scores are stored in the table from the point of view of the player to move, together with
+/// the kind of bound they are with respect to the search window. The stored best move is
+/// searched first.
+/// If the context stops us, the returned score is meaningless.
+pub(crate) fn alpha_beta<E: Evaluator + ?Sized>(
+    depth: u8,
+    ply: usize,
+    state: &Configuration,
+    mut alpha: Score,
+    mut beta: Score,
+    evaluator: &E,
+    context: &mut SearchContext,
+) -> (Score, Option<Movement>) {
+    if context.visit() {
+        return (Score::DRAW, None);
+    }
+    if let Some(score) = Score::terminal(state, ply) {
+        return (score, None);
     }
-    let mut best_score = i8::MIN;
+    if depth == 0 {
+        return (evaluator.evaluate(state), None);
+    }
+    if !state.can_move() {
+        // we have to pass, the opponent plays again (he can, or the game would be over)
+        let (score, _) = alpha_beta(
+            depth - 1,
+            ply + 1,
+            &state.skip_play(),
+            -beta,
+            -alpha,
+            evaluator,
+            context,
+        );
+        return (-score, None);
+    }
+    let table = context.table;
+    let lookup = table.map(|table| table.lookup(state, depth, ply, &mut alpha, &mut beta));
+    let hash_move = match lookup {
+        Some(Lookup::Cutoff(score, movement)) => return (-score, movement),
+        Some(Lookup::Search(hash_move)) => hash_move,
+        None => None,
+    };
+    let window = (alpha, beta);
+    let mut best_score = Score::MIN;
     let mut best_move: Option<Movement> = None;
-    for movement in state.movements() {
-        let next_conf: Configuration = state.play(&movement);
-        let (score, _) = alpha_beta(depth - 1, &next_conf, -beta, -alpha);
+    let movements = context.ordering.order(state, ply, hash_move);
+    for (index, movement) in movements.into_iter().enumerate() {
+        let next_conf = state.play(&movement);
+        let (score, _) = alpha_beta(
+            depth - 1,
+            ply + 1,
+            &next_conf,
+            -beta,
+            -alpha,
+            evaluator,
+            context,
+        );
+        if context.stopped() {
+            return (Score::DRAW, best_move);
+        }
         if score > best_score {
             best_score = score;
             best_move = Some(movement);
             if best_score > alpha {
                 alpha = best_score;
             }
-            if beta < alpha {
+            if alpha >= beta {
+                context.cutoff(movement, index, ply, depth);
                 break;
             }
         }
     }
-    assert_ne!(best_score, i8::MIN);
+    if let Some(table) = table {
+        table.record(state, depth, ply, window, best_score, best_move);
+    }
     (-best_score, best_move)
 }
 
 // Alpha - Beta algorithm in functional programming
+#[allow(dead_code)]
 fn alpha_beta_func(
     depth: u8,
     state: &Configuration,
-    mut alpha: i8,
-    beta: i8,
-) -> (i8, Option<Movement>) {
+    mut alpha: i16,
+    beta: i16,
+) -> (i16, Option<Movement>) {
     if depth == 0 || !state.can_move() {
         return (state.value(), None);
     }
     let result = state
         .movements()
-        .try_fold((i8::MIN, None), |acc, movement| {
+        .try_fold((i16::MIN, None), |acc, movement| {
             let (mut bscore, mut bmove) = acc;
             let (score, _) = alpha_beta_func(depth - 1, &(state.play(&movement)), -beta, -alpha);
             if score > bscore {
@@ -92,203 +186,142 @@ fn alpha_beta_func(
     (-bscore, bmove)
 }
 
-
-// Alpha - Beta algorithm in functional programming
-fn alpha_beta_func_memo(
-    depth: u8,
-    state: &Configuration,
-    mut alpha: i8,
-    beta: i8,
-    memo: &mut HashMap<String, (i8, Movement)>
-) -> (i8, Option<Movement>) {
-    // check the value of the prev iteration
-    if let Some((bscore, bmove)) = memo.get(&state.serialize()) {
-        return (*bscore, Some(*bmove));
-    }
-    if depth == 0 || !state.can_move() {
-        return (state.value(), None);
+impl<E: Evaluator> Strategy for AlphaBeta<E> {
+    fn compute_next_move(
+        &mut self,
+        state: &Configuration,
+        context: &mut SearchContext,
+    ) -> Option<Movement> {
+        if let Some(movement) = solve_endgame(state, context) {
+            return movement;
+        }
+        let depth = context.depth(self.0);
+        if !context.interruptible() {
+            let (_, movement) =
+                alpha_beta(depth, 0, state, Score::MIN, Score::MAX, &self.1, context);
+            return movement;
+        }
+        // we might get stopped : deepen iteratively to always have a move at hand
+        deepen(
+            state,
+            context,
+            depth,
+            |depth, context| {
+                alpha_beta(depth, 0, state, Score::MIN, Score::MAX, &self.1, context).1
+            },
+            |_| {},
+        )
     }
-    let result = state
-        .movements()
-        .try_fold((i8::MIN, None), |acc, movement| {
-            let (mut bscore, mut bmove) = acc;
-            let (score, _) = alpha_beta_func_memo(depth - 1, &(state.play(&movement)), -beta, -alpha, memo);
-            if score > bscore {
-                bscore = score;
-                bmove = Some(movement);
-                if bscore > alpha {
-                    alpha = bscore;
-                }
-                if beta < alpha {
-                    return Err((bscore, bmove));
-                }
-            }
-            Ok((bscore, bmove))
-        });
-    let (bscore, bmove) = match result {
-        Ok((bscore, bmove)) => (bscore, bmove),
-        Err((bscore, bmove)) => (bscore, bmove),
-    };
-    memo.insert(state.serialize(), (-bscore, bmove.unwrap()));
-    (-bscore, bmove)
 }
 
-// Pseudo parallel alpha beta
-fn palpha_beta(
-    depth: u8,
-    state: &Configuration,
-    mut alpha: i8,
-    beta: i8,
-) -> (i8, Option<Movement>) {
-    if depth == 0 || !state.can_move() {
-        return (state.value(), None);
-    }
-    let (bmove, (bscore, _)) = state
-        .movements()
-        .collect::<Vec<_>>()
-        .into_par_iter()
-        .map(|movement| (movement, alpha_beta_func(depth - 1, &(state.play(&movement)), -beta, -alpha)))
-        .max_by_key(|&(_, (value, _))| value)
-        .unwrap();
-    (-bscore, Some(bmove))
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+    use crate::fixtures::{last_blue_blob, red_stuck, three_cells_board};
+    use crate::positions::Positions;
+    use crate::strategy::SearchLimits;
 
-fn alpha_beta_sorted(
-    depth: u8,
-    state: &Configuration,
-    mut alpha: i8,
-    beta: i8,
-) -> (i8, Option<Movement>) {
-    if depth == 0 || !state.can_move() {
-        return (state.value(), None);
-    }
-    let mut best_score = i8::MIN;
-    let mut best_move: Option<Movement> = None;
-    let mut sorted_moves: Vec<Movement> = state.movements().collect::<Vec<Movement>>();
-    sorted_moves.sort_by_key(|m| -state.play(m).value());
-    for movement in sorted_moves.into_iter() {
-        let next_conf: Configuration = state.play(&movement);
-        let (score, _) = alpha_beta_sorted(depth - 1, &next_conf, -beta, -alpha);
-        if score > best_score {
-            best_score = score;
-            best_move = Some(movement);
-            if best_score > alpha {
-                alpha = best_score;
-            }
-            if beta < alpha {
-                break;
+    #[test]
+    fn table_does_not_change_scores() {
+        let board = Board::default();
+        let mut state = Configuration::new(&board);
+        for turn in 0..8 {
+            // entries from previous turns are deeper : they would change (improve) the scores
+            let table = TranspositionTable::new(1);
+            for depth in 1..4 {
+                let mut context = SearchContext::default();
+                let (expected, _) = alpha_beta(
+                    depth,
+                    0,
+                    &state,
+                    Score::MIN,
+                    Score::MAX,
+                    &Material,
+                    &mut context,
+                );
+                let mut context = SearchContext::default().with_table(&table);
+                let (score, _) = alpha_beta(
+                    depth,
+                    0,
+                    &state,
+                    Score::MIN,
+                    Score::MAX,
+                    &Material,
+                    &mut context,
+                );
+                assert_eq!(expected, score);
             }
+            let movement = state.movements().nth(turn * 3).unwrap();
+            state.apply_movement(&movement);
         }
     }
-    assert_ne!(best_score, i8::MIN);
-    (-best_score, best_move)
-}
 
-fn negascout(depth: u8, state: &Configuration, mut alpha: i8, beta: i8,
-            memo: Option<&mut HashMap<String, (i8, Movement)>>
-) -> (i8, Option<Movement>) {
-    if depth == 0 || !state.can_move() {
-        return (state.value(), None);
+    #[test]
+    fn quickest_win_is_chosen() {
+        let board = Board::default();
+        let state = last_blue_blob(&board);
+        for depth in 1..5 {
+            let mut context = SearchContext::default();
+            let (score, movement) = alpha_beta(
+                depth,
+                0,
+                &state,
+                Score::MIN,
+                Score::MAX,
+                &Material,
+                &mut context,
+            );
+            assert_eq!(-score, Score::win_in(1));
+            assert!(state.play(&movement.unwrap()).game_over());
+        }
     }
 
-    // check the value of the prev iteration
-    let memo = memo.unwrap();
-    if let Some((bscore, bmove)) = memo.get(&state.serialize()) {
-        return (*bscore, Some(*bmove));
-    }
-    let result = state
-        .movements()
-        .try_fold((i8::MIN, None), |acc, movement| {
-            let (mut bscore, mut bmove) = acc;
-            let (score, _) = alpha_beta_func(depth - 1, &(state.play(&movement)), -beta, -alpha);
-            if score > bscore {
-                bscore = score;
-                bmove = Some(movement);
-                if bscore > alpha {
-                    alpha = bscore;
-                }
-                if beta < alpha {
-                    return Err((bscore, bmove));
-                }
-            }
-            Ok((bscore, bmove))
-        });
-    let (bscore, bmove) = match result {
-        Ok((bscore, bmove)) => (bscore, bmove),
-        Err((bscore, bmove)) => (bscore, bmove),
-    };
-    memo.insert(state.serialize(), (-bscore, bmove.unwrap()));
-    (-bscore, bmove)
-}
+    #[test]
+    fn passes_are_searched() {
+        let board = three_cells_board();
+        let state = red_stuck(&board);
 
-// static mut ets: Duration = Duration::from_secs(0);
-// static mut etn: Duration = Duration::from_secs(0);
-// static mut etnf: Duration = Duration::from_secs(0);
-// static mut etp: Duration = Duration::from_secs(0);
-// static mut etm: Duration = Duration::from_secs(0);
-// static mut count: u32 = 0;
-impl Strategy for AlphaBeta {
-    fn compute_next_move(&mut self, state: &Configuration,
-                    memo: Option<&mut HashMap<String, (i8, Movement)>>,
-    ) -> Option<Movement> {
-
-        // let start_time = Instant::now();
-        // let (s0, mv0) = palpha_beta(self.0, state, i8::MIN + 1, i8::MAX);
-        // let end_time = Instant::now();
-        // unsafe {
-        //     count += 1;
-        //     etp += end_time.duration_since(start_time);
-        //     println!("avg p {:?}", etp/count);
-        // }
-
-        // let start_time = Instant::now();
-        // let (s1, mv1) = alpha_beta_sorted(self.0, state, i8::MIN + 1, i8::MAX);
-        // let end_time = Instant::now();
-        // unsafe {
-        //     ets += end_time.duration_since(start_time);
-        //     println!("avg sorted {:?}", ets/count);
-        // }
-
-        // let start_time = Instant::now();
-        // let (_, mv2) = alpha_beta(self.0, state, i8::MIN + 1, i8::MAX);
-        // let end_time = Instant::now();
-        // let duration = end_time.duration_since(start_time);
-        // if duration.as_secs_f32() > 1.0 {
-        //     unreachable!("Duration was greater than 1 second");
-        // }
-        // unsafe {
-        //     etn += end_time.duration_since(start_time);
-        //     println!("avg normal {:?}", etn/count);
-        // }
+        let mut context = SearchContext::default();
+        let (score, movement) = alpha_beta(
+            3,
+            0,
+            &state,
+            Score::MIN,
+            Score::MAX,
+            &Material,
+            &mut context,
+        );
+        assert_eq!(movement, None);
+        assert_eq!(-score, Score::win_in(3));
+    }
 
-        // let start_time = Instant::now();
-        // let (s2, mv2) = alpha_beta_func(self.0, state, i8::MIN + 1, i8::MAX);
-        // let end_time = Instant::now();
-        // unsafe {
-        //     etnf += end_time.duration_since(start_time);
-        //     println!("avg normal func {:?}", etnf/count);
-        // }
+    #[test]
+    fn inexact_solutions_are_searched() {
+        // only cells 0 (red), 2, 4 and 6 (blue) are playable : players can only jump, forever
+        let board = Board::new(Positions::from_cells(&[0, 2, 4, 6]).invert());
+        let state =
+            Configuration::deserialize(&format!("0r     b{}", " ".repeat(57)), &board).unwrap();
+        let solution = endgame::solve(&state, &mut Default::default()).unwrap();
+        assert!(!solution.exact);
+        assert_eq!(solve_endgame(&state, &mut Default::default()), None);
+        let mut context = SearchContext::default();
+        let movement = AlphaBeta(3, Material).compute_next_move(&state, &mut context);
+        assert!(movement.is_some());
 
-        // let mut memo: HashMap<String, (i8, Movement)> = HashMap::new();
-        // let start_time = Instant::now();
-        // let (s3, mv3) = alpha_beta_func_memo(self.0, state, i8::MIN + 1, i8::MAX, &mut memo);
-        // let end_time = Instant::now();
-        // unsafe {
-        //     etm += end_time.duration_since(start_time);
-        //     println!("avg memo {:?}", etm/count);
-        // }
+        // unless the game ends before the horizon
+        let mut context = SearchContext::new(SearchLimits::default().plies(4));
+        assert!(solve_endgame(&state, &mut context).is_some());
+    }
 
-        // assert!(s0 == s1 && s1 == s2);
-        // let mv = if state.current_player {
-        //     // blue_player
-        // let (_, mv) = negascout(self.0, state, i8::MIN + 1, i8::MAX, memo);
-        //     mv
-        // } else {
-        //     // red player
-        //     let (_, mv) = alpha_beta_sorted(self.0, state, i8::MIN + 1, i8::MAX);
-        //     mv
-        // };
-        let (_, mv2) = alpha_beta(self.0, state, i8::MIN + 1, i8::MAX);
-        mv2
+    #[test]
+    fn limits_are_respected() {
+        let board = Board::default();
+        let state = Configuration::new(&board);
+        let mut context = SearchContext::new(SearchLimits::default().nodes(5000));
+        let movement = AlphaBeta(12, Material).compute_next_move(&state, &mut context);
+        assert!(context.stopped());
+        assert!(context.nodes() <= 5001);
+        assert!(state.check_move(&movement.unwrap()));
     }
 }
@@ -0,0 +1,311 @@
+//! Opening books.
+//! A book associates to positions (identified by their zobrist hash) candidate moves with
+//! weights. Hashes do not take holes into account so a book is only valid for the board it was
+//! built on : the board is stored with the book and checked before playing from it.
+//!
+//! Books are stored as json files.
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+use serde_derive::{Deserialize, Serialize};
+
+use super::context::SearchContext;
+use super::mcts::Random;
+use super::Strategy;
+use crate::board::Board;
+use crate::configuration::{Configuration, Movement};
+use crate::positions::Positions;
+
+/// Candidate moves of one position.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    hash: u64,
+    moves: Vec<(Movement, u32)>,
+}
+
+/// On disk format.
+#[derive(Serialize, Deserialize)]
+struct BookFile {
+    holes: Vec<u64>,
+    positions: Vec<Entry>,
+}
+
+/// Opening book for a given board.
+pub struct Book {
+    holes: Positions,
+    positions: HashMap<u64, Vec<(Movement, u32)>>,
+}
+
+impl Book {
+    /// New empty book for given board.
+    pub fn new(board: &Board) -> Self {
+        Book {
+            holes: board.holes,
+            positions: HashMap::new(),
+        }
+    }
+
+    /// Number of positions in the book.
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Is the book empty ?
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Add given weight to given move in given position.
+    pub fn add(&mut self, state: &Configuration, movement: Movement, weight: u32) {
+        let moves = self.positions.entry(state.hash()).or_default();
+        match moves.iter_mut().find(|(m, _)| *m == movement) {
+            Some((_, total)) => *total = total.saturating_add(weight),
+            None => moves.push((movement, weight)),
+        }
+    }
+
+    /// Candidate moves (with their weights) for given position.
+    /// Nothing if the position is not in the book or is on another board.
+    pub fn moves(&self, state: &Configuration) -> &[(Movement, u32)] {
+        if state.board().holes != self.holes {
+            return &[];
+        }
+        self.positions
+            .get(&state.hash())
+            .map_or(&[], |moves| moves.as_slice())
+    }
+
+    /// Pick a legal candidate move at random, proportionally to weights.
+    fn choose(&self, state: &Configuration, random: &mut Random) -> Option<Movement> {
+        // hash collisions are rare but possible : check moves before playing them
+        let moves: Vec<_> = self
+            .moves(state)
+            .iter()
+            .filter(|(movement, weight)| *weight > 0 && state.check_move(movement))
+            .collect();
+        let total: u64 = moves.iter().map(|(_, weight)| u64::from(*weight)).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut target = random.next() % total;
+        for (movement, weight) in moves {
+            if target < u64::from(*weight) {
+                return Some(*movement);
+            }
+            target -= u64::from(*weight);
+        }
+        unreachable!()
+    }
+
+    /// Build a book for the given number of plies from given position.
+    /// Whoever is to move, the move chosen by `strategy` is stored and we look at all the
+    /// opponent's answers. This is done for both players, so the number of searches grows as
+    /// the number of moves to the power of half the plies.
+    pub fn generate<S: Strategy>(start: &Configuration, plies: usize, strategy: &mut S) -> Self {
+        let mut book = Book::new(start.board());
+        for us in [false, true] {
+            book.expand(start, plies, us, strategy);
+        }
+        book
+    }
+
+    fn expand<S: Strategy>(
+        &mut self,
+        state: &Configuration,
+        plies: usize,
+        us: bool,
+        strategy: &mut S,
+    ) {
+        if plies == 0 || state.game_over() {
+            return;
+        }
+        if state.current_player != us {
+            let movements: Vec<Movement> = state.movements().collect();
+            if movements.is_empty() {
+                self.expand(&state.skip_play(), plies - 1, us, strategy);
+            }
+            for movement in movements {
+                self.expand(&state.play(&movement), plies - 1, us, strategy);
+            }
+            return;
+        }
+        let known = self.moves(state).first().map(|&(movement, _)| movement);
+        let movement = known.or_else(|| {
+            let movement = strategy.compute_next_move(state, &mut SearchContext::default());
+            if let Some(movement) = movement {
+                self.add(state, movement, 1);
+            }
+            movement
+        });
+        match movement {
+            Some(movement) => self.expand(&state.play(&movement), plies - 1, us, strategy),
+            None => self.expand(&state.skip_play(), plies - 1, us, strategy),
+        }
+    }
+
+    /// Learn from a finished game played from `start` : the first `plies` moves of the winner
+    /// (both players on a draw) get more weight. `None` moves are passes.
+    pub fn learn(
+        &mut self,
+        start: &Configuration,
+        moves: &[Option<Movement>],
+        winner: Option<bool>,
+        plies: usize,
+    ) {
+        let mut state = *start;
+        for movement in moves.iter().take(plies) {
+            match movement {
+                Some(movement) => {
+                    if winner.is_none_or(|winner| winner == state.current_player) {
+                        self.add(&state, *movement, 1);
+                    }
+                    state.apply_movement(movement);
+                }
+                None => {
+                    state.apply_skip();
+                }
+            }
+        }
+    }
+
+    /// Load book from given json file.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file: BookFile = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        let holes = file.holes.try_into().map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidData, "invalid holes in book")
+        })?;
+        Ok(Book {
+            holes: Positions(holes),
+            positions: file
+                .positions
+                .into_iter()
+                .map(|entry| (entry.hash, entry.moves))
+                .collect(),
+        })
+    }
+
+    /// Save book into given json file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut positions: Vec<Entry> = self
+            .positions
+            .iter()
+            .map(|(&hash, moves)| Entry {
+                hash,
+                moves: moves.clone(),
+            })
+            .collect();
+        // stable output for diffs
+        positions.sort_by_key(|entry| entry.hash);
+        let file = BookFile {
+            holes: self.holes.0.to_vec(),
+            positions,
+        };
+        serde_json::to_writer(BufWriter::new(File::create(path)?), &file)?;
+        Ok(())
+    }
+}
+
+/// Play from an opening book while possible, then let the inner strategy play.
+pub struct BookStrategy<S> {
+    book: Book,
+    inner: S,
+    random: Random,
+}
+
+impl<S: Strategy> BookStrategy<S> {
+    /// Play from given book, falling back to given strategy.
+    pub fn new(book: Book, inner: S) -> Self {
+        BookStrategy {
+            book,
+            inner,
+            random: Random(0x2545_f491_4f6c_dd1d),
+        }
+    }
+
+    /// Seed the random generator choosing between book moves.
+    pub fn seed(self, seed: u64) -> Self {
+        BookStrategy {
+            random: Random(seed | 1),
+            ..self
+        }
+    }
+}
+
+impl<S: Strategy> fmt::Display for BookStrategy<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (with book)", self.inner)
+    }
+}
+
+impl<S: Strategy> Strategy for BookStrategy<S> {
+    fn compute_next_move(
+        &mut self,
+        state: &Configuration,
+        context: &mut SearchContext,
+    ) -> Option<Movement> {
+        self.book
+            .choose(state, &mut self.random)
+            .or_else(|| self.inner.compute_next_move(state, context))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluation::Material;
+    use crate::strategy::Greedy;
+
+    #[test]
+    fn book_moves_are_played_then_inner_strategy() {
+        let board = Board::default();
+        let start = Configuration::new(&board);
+        let mut book = Book::new(&board);
+        let movement = Movement::Jump(0, 2);
+        book.add(&start, movement, 3);
+        let mut strategy = BookStrategy::new(book, Greedy(Material));
+        assert_eq!(
+            strategy.compute_next_move(&start, &mut Default::default()),
+            Some(movement)
+        );
+        let next = start.play(&movement);
+        let fallback = strategy.compute_next_move(&next, &mut Default::default());
+        assert!(next.check_move(&fallback.unwrap()));
+        // not on another board
+        let other = Board::new(Positions::single(27));
+        assert!(strategy.book.moves(&Configuration::new(&other)).is_empty());
+    }
+
+    #[test]
+    fn generated_book_survives_save_and_load() {
+        let board = Board::default();
+        let start = Configuration::new(&board);
+        let book = Book::generate(&start, 2, &mut Greedy(Material));
+        // red's move at the start and blue's answers to all red moves
+        assert_eq!(book.len(), 1 + start.movements().count());
+        let path = std::env::temp_dir().join(format!("blobwar-book-{}.json", std::process::id()));
+        book.save(&path).unwrap();
+        let loaded = Book::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.len(), book.len());
+        assert_eq!(loaded.moves(&start), book.moves(&start));
+    }
+
+    #[test]
+    fn winners_moves_are_learned() {
+        let board = Board::default();
+        let start = Configuration::new(&board);
+        let moves = [
+            Some(Movement::Duplicate(13)),
+            None,
+            Some(Movement::Duplicate(26)),
+        ];
+        let mut book = Book::new(&board);
+        book.learn(&start, &moves, Some(false), 10);
+        assert_eq!(book.moves(&start), &[(Movement::Duplicate(13), 1)]);
+        assert_eq!(book.len(), 2);
+    }
+}
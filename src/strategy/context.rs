@@ -0,0 +1,235 @@
+//! Limits and shared resources given to strategies when computing a move.
+use super::ordering::MoveOrdering;
+use super::tt::TranspositionTable;
+use crate::configuration::{Configuration, Movement};
+use std::cmp::min;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Check the clock only once every that many nodes.
+const CLOCK_CHECK_PERIOD: u64 = 1024;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+/// Limits imposed on a search. `None` means unlimited.
+pub struct SearchLimits {
+    /// Maximum time spent searching.
+    pub time: Option<Duration>,
+    /// Maximum search depth.
+    pub depth: Option<u8>,
+    /// Maximum number of visited nodes.
+    pub nodes: Option<u64>,
+    /// Plies left before the game ends on the ply limit of the board (see `Rules`).
+    pub plies: Option<u32>,
+}
+
+impl SearchLimits {
+    /// Limit search time.
+    pub fn time(self, time: Duration) -> Self {
+        SearchLimits {
+            time: Some(time),
+            ..self
+        }
+    }
+
+    /// Limit search depth.
+    pub fn depth(self, depth: u8) -> Self {
+        SearchLimits {
+            depth: Some(depth),
+            ..self
+        }
+    }
+
+    /// Limit number of visited nodes.
+    pub fn nodes(self, nodes: u64) -> Self {
+        SearchLimits {
+            nodes: Some(nodes),
+            ..self
+        }
+    }
+
+    /// Set the number of plies left before the game ends.
+    pub fn plies(self, plies: u32) -> Self {
+        SearchLimits {
+            plies: Some(plies),
+            ..self
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+/// Statistics on a search, to measure the efficiency of pruning.
+pub struct SearchStats {
+    /// Visited nodes.
+    pub nodes: u64,
+    /// Nodes where the search was cut (beta cutoff).
+    pub cutoffs: u64,
+    /// Cutoffs obtained on the first searched move (the higher the better the ordering).
+    pub first_move_cutoffs: u64,
+}
+
+/// Everything a strategy might need besides the `Configuration` : limits, an optional shared
+/// `TranspositionTable` and an optional stop flag which can be raised from another thread.
+/// It also holds the move ordering heuristics and keeps statistics.
+pub struct SearchContext<'a> {
+    /// Limits for this search.
+    pub limits: SearchLimits,
+    /// Shared transposition table (if any).
+    pub table: Option<&'a TranspositionTable>,
+    /// Move ordering heuristics (learned during the search).
+    pub ordering: MoveOrdering,
+    stop: Option<&'a AtomicBool>,
+    /// Nodes visited by all threads searching together, checked against the node limit.
+    shared_nodes: Option<&'a AtomicU64>,
+    start: Instant,
+    stats: SearchStats,
+    stopped: bool,
+}
+
+impl<'a> Default for SearchContext<'a> {
+    fn default() -> Self {
+        SearchContext::new(Default::default())
+    }
+}
+
+impl<'a> SearchContext<'a> {
+    /// New context with given limits, starting the clock now.
+    pub fn new(limits: SearchLimits) -> Self {
+        SearchContext {
+            limits,
+            table: None,
+            ordering: Default::default(),
+            stop: None,
+            shared_nodes: None,
+            start: Instant::now(),
+            stats: Default::default(),
+            stopped: false,
+        }
+    }
+
+    /// Share given `TranspositionTable` with the search.
+    pub fn with_table(self, table: &'a TranspositionTable) -> Self {
+        SearchContext {
+            table: Some(table),
+            ..self
+        }
+    }
+
+    /// Use given move ordering heuristics.
+    pub fn with_ordering(self, ordering: MoveOrdering) -> Self {
+        SearchContext { ordering, ..self }
+    }
+
+    /// Stop the search as soon as given flag is raised.
+    pub fn with_stop(self, stop: &'a AtomicBool) -> Self {
+        SearchContext {
+            stop: Some(stop),
+            ..self
+        }
+    }
+
+    /// Count nodes in given counter, shared with other threads : the node limit applies to
+    /// all of them together.
+    pub(crate) fn with_shared_nodes(self, nodes: &'a AtomicU64) -> Self {
+        SearchContext {
+            shared_nodes: Some(nodes),
+            ..self
+        }
+    }
+
+    /// Stop flag given with `with_stop` (if any).
+    pub(crate) fn stop_flag(&self) -> Option<&'a AtomicBool> {
+        self.stop
+    }
+
+    /// Account for statistics of searches done on our behalf (by other threads).
+    pub(crate) fn add_stats(&mut self, stats: SearchStats) {
+        self.stats.nodes += stats.nodes;
+        self.stats.cutoffs += stats.cutoffs;
+        self.stats.first_move_cutoffs += stats.first_move_cutoffs;
+    }
+
+    /// Depth to search for a strategy wishing to search at given depth.
+    pub fn depth(&self, wanted: u8) -> u8 {
+        self.limits.depth.map_or(wanted, |limit| min(limit, wanted))
+    }
+
+    /// Can the search be interrupted before completion ?
+    pub fn interruptible(&self) -> bool {
+        self.limits.time.is_some() || self.limits.nodes.is_some() || self.stop.is_some()
+    }
+
+    /// Count a new visited node and return if the search should stop now.
+    /// Once true, it stays true.
+    pub fn visit(&mut self) -> bool {
+        if self.stopped {
+            return true;
+        }
+        self.stats.nodes += 1;
+        let own_nodes = self.stats.nodes;
+        let nodes = self.shared_nodes.map_or(own_nodes, |shared| {
+            shared.fetch_add(1, Ordering::Relaxed) + 1
+        });
+        self.stopped = self.stop.is_some_and(|stop| stop.load(Ordering::Relaxed))
+            || self.limits.nodes.is_some_and(|limit| nodes > limit)
+            || (own_nodes.is_multiple_of(CLOCK_CHECK_PERIOD)
+                && self.limits.time.is_some_and(|limit| self.elapsed() >= limit));
+        self.stopped
+    }
+
+    /// Did we hit a limit ? If so, results of the current search are unreliable.
+    pub fn stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// Record a cutoff caused by the given move (searched at given index) at given ply and
+    /// remaining depth.
+    pub fn cutoff(&mut self, movement: Movement, index: usize, ply: usize, depth: u8) {
+        self.stats.cutoffs += 1;
+        if index == 0 {
+            self.stats.first_move_cutoffs += 1;
+        }
+        self.ordering.cutoff(movement, ply, depth);
+    }
+
+    /// Number of nodes visited so far.
+    pub fn nodes(&self) -> u64 {
+        self.stats.nodes
+    }
+
+    /// Statistics on the search so far.
+    pub fn stats(&self) -> SearchStats {
+        self.stats
+    }
+
+    /// Time elapsed since the context creation.
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// Search at increasing depths (up to `max_depth`) until the context stops us.
+/// `search` computes the move for a given depth and `report` is called with the result of each
+/// completed iteration.
+/// Return the move of the last completed iteration (or any move if none completed).
+pub(crate) fn deepen<S, R>(
+    state: &Configuration,
+    context: &mut SearchContext,
+    max_depth: u8,
+    mut search: S,
+    mut report: R,
+) -> Option<Movement>
+where
+    S: FnMut(u8, &mut SearchContext) -> Option<Movement>,
+    R: FnMut(Option<Movement>),
+{
+    let mut best_move = None;
+    for depth in 1..=max_depth {
+        let movement = search(depth, context);
+        if context.stopped() {
+            break;
+        }
+        best_move = movement;
+        report(best_move);
+    }
+    best_move.or_else(|| state.movements().next())
+}
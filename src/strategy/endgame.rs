@@ -0,0 +1,209 @@
+//! Endgame solver.
+//! When only a few empty cells remain we can search until the end of the game and obtain the
+//! final blob difference under perfect play instead of an evaluation.
+//!
+//! Jumps do not fill the board so games can in theory last forever (players jumping back and
+//! forth). The solver only searches up to a horizon (a few plies more than empty cells) and
+//! scores the lines reaching it with the current blob difference. Results are flagged as exact
+//! when no line was cut that way.
+//! The horizon is also capped by the plies left before the ply limit of the board (given in
+//! the `SearchLimits`) : the game really ends there, so lines reaching it are exact.
+use super::context::{SearchContext, SearchLimits};
+use super::tt::{Lookup, TranspositionTable};
+use crate::configuration::{Configuration, Movement};
+use crate::score::Score;
+
+/// Positions with at most that many empty cells are solved.
+pub const THRESHOLD: usize = 5;
+
+/// Maximal number of nodes a solve may visit (on top of the context's own limits).
+pub const MAX_NODES: u64 = 500_000;
+
+/// Plies searched : one for each empty cell and some more for passes and jumps.
+fn horizon(empty_cells: usize) -> u8 {
+    (empty_cells + 4) as u8
+}
+
+/// Is given configuration late enough in the game to be solved ?
+pub fn applies(state: &Configuration) -> bool {
+    state.empty_cells().count() <= THRESHOLD
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+/// Result of a solve.
+pub struct Solution {
+    /// Final blob difference for the player to move.
+    pub difference: i16,
+    /// Move to play to achieve it (`None` if we have to pass).
+    pub movement: Option<Movement>,
+    /// Does the difference take all possible lines into account (no line reached the horizon) ?
+    pub exact: bool,
+}
+
+/// Search state of a solve.
+struct Solver<'c, 'a> {
+    context: &'c mut SearchContext<'a>,
+    table: TranspositionTable,
+    exact: bool,
+    /// Is the horizon the ply limit of the game ?
+    final_horizon: bool,
+}
+
+impl Solver<'_, '_> {
+    /// Final blob difference under perfect play from the point of view of the player who just
+    /// moved (as a `Score` to use the table).
+    fn search(
+        &mut self,
+        horizon: u8,
+        ply: usize,
+        state: &Configuration,
+        mut alpha: Score,
+        mut beta: Score,
+    ) -> (Score, Option<Movement>) {
+        if self.context.visit() {
+            return (Score::DRAW, None);
+        }
+        if state.game_over() {
+            return (state.value().into(), None);
+        }
+        if horizon == 0 {
+            self.exact &= self.final_horizon;
+            return (state.value().into(), None);
+        }
+        if !state.can_move() {
+            let skipped = state.skip_play();
+            let (score, _) = self.search(horizon - 1, ply + 1, &skipped, -beta, -alpha);
+            return (-score, None);
+        }
+        // up to the ply limit, values depend on the exact number of plies left
+        let lookup = if self.final_horizon {
+            self.table
+                .lookup_same_depth(state, horizon, ply, &mut alpha, &mut beta)
+        } else {
+            self.table
+                .lookup(state, horizon, ply, &mut alpha, &mut beta)
+        };
+        let hash_move = match lookup {
+            Lookup::Cutoff(score, movement) => return (-score, movement),
+            Lookup::Search(hash_move) => hash_move,
+        };
+        let window = (alpha, beta);
+        let mut best_score = Score::MIN;
+        let mut best_move = None;
+        let movements = self.context.ordering.order(state, ply, hash_move);
+        for (index, movement) in movements.into_iter().enumerate() {
+            let next_conf = state.play(&movement);
+            let (score, _) = self.search(horizon - 1, ply + 1, &next_conf, -beta, -alpha);
+            if self.context.stopped() {
+                return (Score::DRAW, best_move);
+            }
+            if score > best_score {
+                best_score = score;
+                best_move = Some(movement);
+                if best_score > alpha {
+                    alpha = best_score;
+                }
+                if alpha >= beta {
+                    self.context.cutoff(movement, index, ply, horizon);
+                    break;
+                }
+            }
+        }
+        self.table.record(state, horizon, ply, window, best_score, best_move);
+        (-best_score, best_move)
+    }
+}
+
+/// Solve given configuration : return the final blob difference under perfect play (from the
+/// point of view of the player to move) and the move achieving it.
+/// Return `None` if the search was stopped by the context's limits or the solver's node budget.
+pub fn solve(state: &Configuration, context: &mut SearchContext) -> Option<Solution> {
+    let nodes = context.limits.nodes.map_or(MAX_NODES, |n| n.min(MAX_NODES));
+    let limits = SearchLimits {
+        time: context.limits.time.map(|t| t.saturating_sub(context.elapsed())),
+        nodes: Some(nodes),
+        ..context.limits
+    };
+    let mut inner = SearchContext::new(limits);
+    if let Some(stop) = context.stop_flag() {
+        inner = inner.with_stop(stop);
+    }
+    let mut solver = Solver {
+        context: &mut inner,
+        table: TranspositionTable::new(1),
+        exact: true,
+        final_horizon: false,
+    };
+    let mut horizon = horizon(state.empty_cells().count());
+    let plies_left = context.limits.plies;
+    if let Some(plies) = plies_left.filter(|&plies| plies <= horizon as u32) {
+        horizon = plies as u8;
+        solver.final_horizon = true;
+    }
+    let (score, movement) = solver.search(horizon, 0, state, Score::MIN, Score::MAX);
+    let exact = solver.exact;
+    context.add_stats(inner.stats());
+    if inner.stopped() {
+        return None;
+    }
+    Some(Solution {
+        difference: -score.value(),
+        movement,
+        exact,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+    use crate::fixtures::{red_stuck, three_cells_board};
+    use crate::positions::Positions;
+
+    #[test]
+    fn solves_with_passes() {
+        let board = three_cells_board();
+        let state = red_stuck(&board);
+
+        assert!(applies(&state));
+        let solution = solve(&state, &mut Default::default()).unwrap();
+        assert_eq!(
+            solution,
+            Solution {
+                difference: 1,
+                movement: None,
+                exact: true
+            }
+        );
+    }
+
+    #[test]
+    fn stops_on_the_ply_limit() {
+        // as in solves_with_passes, but the game ends once red passed
+        let board = three_cells_board();
+        let state = red_stuck(&board);
+        let mut context = SearchContext::new(SearchLimits::default().plies(1));
+        let solution = solve(&state, &mut context).unwrap();
+        assert_eq!(
+            solution,
+            Solution {
+                difference: 0,
+                movement: None,
+                exact: true
+            }
+        );
+    }
+
+    #[test]
+    fn finds_the_winning_jump() {
+        // only the first four cells are playable : red can jump next to blue and convert it, or
+        // duplicate and lose its new blob
+        let board = Board::new(Positions::from_cells(&[0, 1, 2, 3]).invert());
+        let state =
+            Configuration::deserialize(&format!("0r  b{}", " ".repeat(60)), &board).unwrap();
+        let solution = solve(&state, &mut Default::default()).unwrap();
+        assert_eq!(solution.movement, Some(Movement::Jump(0, 2)));
+        assert_eq!(solution.difference, 2);
+        assert!(solution.exact);
+    }
+}
@@ -1,31 +1,34 @@
 //! Dumb greedy algorithm.
+use super::context::SearchContext;
 use super::Strategy;
-use crate::{configuration::{Configuration, Movement}, positions::Position};
+use crate::configuration::{Configuration, Movement};
+use crate::evaluation::{Evaluator, Material};
+use crate::score::Score;
 use std::fmt;
-use std::collections::HashMap;
 
 /// Dumb algorithm.
 /// Amongst all possible movements return the one which yields the configuration with the best
-/// immediate value.
-pub struct Greedy();
+/// immediate value (according to given `Evaluator`), or which ends the game if we win.
+pub struct Greedy<E = Material>(pub E);
 
-impl fmt::Display for Greedy {
+impl<E: Evaluator> fmt::Display for Greedy<E> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Greedy")
+        write!(f, "Greedy ({})", self.0)
     }
 }
 
-impl Strategy for Greedy {
+impl<E: Evaluator> Strategy for Greedy<E> {
     fn compute_next_move(
         &mut self,
         state: &Configuration,
-        _memo: Option<&mut HashMap<String, (i8, Movement)>>,
+        _context: &mut SearchContext,
     ) -> Option<Movement> {
-        let mut best_score = i8::MIN;
+        let mut best_score = Score::MIN;
         let mut best_move: Option<Movement> = None;
         for movement in state.movements() {
-            let next_conf : Configuration = state.play(&movement);
-            let score = next_conf.value();
+            let next_conf: Configuration = state.play(&movement);
+            let score =
+                Score::terminal(&next_conf, 1).unwrap_or_else(|| self.0.evaluate(&next_conf));
             if score > best_score {
                 best_score = score;
                 best_move = Some(movement);
@@ -1,11 +1,11 @@
 //! Human player.
-use super::Strategy;
+use super::context::SearchContext;
+use super::{Decision, Strategy};
 use crate::configuration::{Configuration, Movement};
-use crate::positions::{BoardPosition, Position};
+use crate::record::ForfeitReason;
 use std::fmt;
 use std::io;
 use std::io::BufRead;
-use std::collections::HashMap;
 
 /// Let a human enter moves on stdin.
 pub struct Human();
@@ -15,59 +15,58 @@ impl fmt::Display for Human {
     }
 }
 
-fn ask_cell() -> Result<(u8, u8), io::Error> {
+/// Read a move in algebraic notation (c3 or a1-c3) or a take back request.
+fn ask_move() -> Result<Decision, io::Error> {
+    println!("enter move (c3 to duplicate, a1-c3 to jump or undo to take back your last move)");
     let mut line = String::new();
     let stdin = io::stdin();
-    stdin.lock().read_line(&mut line)?;
-    let mut words = line.split_whitespace();
-    let y: u8 = words
-        .next()
-        .unwrap()
-        .parse()
-        .map_err(|_| io::ErrorKind::Other)?;
-    let x: u8 = words
-        .next()
-        .unwrap()
-        .parse()
-        .map_err(|_| io::ErrorKind::Other)?;
-    Ok((x, y))
-}
-
-fn ask_move() -> Result<Movement, io::Error> {
-    println!("enter start point");
-    let (sx, sy) = ask_cell()?;
-    let start_position = Position::from_2d(sx, sy);
-    println!("enter end point");
-    let (ex, ey) = ask_cell()?;
-    let end_position = Position::from_2d(ex, ey);
-    match start_position.distance_to(end_position) {
-        1 => Ok(Movement::Duplicate(end_position)),
-        2 => Ok(Movement::Jump(start_position, end_position)),
-        _ => {
-            println!("invalid movement");
-            ask_move()
-        }
+    if stdin.lock().read_line(&mut line)? == 0 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
     }
+    if line.trim() == "undo" {
+        return Ok(Decision::TakeBack);
+    }
+    line.parse()
+        .map(|movement| Decision::Play(Some(movement)))
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
 }
 
 impl Strategy for Human {
     fn compute_next_move(
         &mut self,
         configuration: &Configuration,
-        _memo: Option<&mut HashMap<String, (i8, Movement)>>,
+        context: &mut SearchContext,
     ) -> Option<Movement> {
+        loop {
+            match self.decide(configuration, context) {
+                Decision::Play(movement) => return movement,
+                Decision::TakeBack => println!("moves cannot be taken back here"),
+                Decision::Forfeit(_) => return None,
+            }
+        }
+    }
+
+    fn decide(&mut self, configuration: &Configuration, _context: &mut SearchContext) -> Decision {
         if configuration.movements().next().is_some() {
             loop {
-                if let Ok(movement) = ask_move() {
-                    if configuration.check_move(&movement) {
-                        return Some(movement);
-                    } else {
-                        println!("invalid movement (are you playing your color ?)");
+                match ask_move() {
+                    Ok(Decision::Play(Some(movement))) => {
+                        if configuration.check_move(&movement) {
+                            return Decision::Play(Some(movement));
+                        } else {
+                            println!("invalid movement (are you playing your color ?)");
+                        }
+                    }
+                    Ok(decision) => return decision,
+                    // nobody is left to play
+                    Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                        return Decision::Forfeit(ForfeitReason::Disconnected)
                     }
+                    Err(e) => println!("{}", e),
                 }
             }
         } else {
-            None
+            Decision::Play(None)
         }
     }
 }
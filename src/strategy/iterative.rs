@@ -1,15 +1,32 @@
 //! Generic iterative deepening strategies (with variable algorithms).
+use std::cmp::min;
+use std::env;
 use std::fmt;
 
 use std::io;
 use std::process::Command;
-use std::thread::sleep;
-use std::time::Duration;
-use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::sync::{Mutex, Once};
+use std::thread::{scope, sleep};
+use std::time::{Duration, Instant};
 
+use super::alphabeta::alpha_beta_deepening;
+use super::context::{SearchContext, SearchLimits};
+use super::minmax::min_max_deepening;
+use super::tt::TranspositionTable;
 use super::Strategy;
 use crate::configuration::{Configuration, Movement};
-use crate::shmem::AtomicMove;
+use crate::shmem::{remove_orphan_segments, AtomicMove};
+
+/// Name of the binary running anytime algorithms in another process.
+const ANYTIME_BINARY: &str = "blobwar_iterative_deepening";
+
+/// How often a threaded search checks the context's stop flag.
+const STOP_CHECK_PERIOD: Duration = Duration::from_millis(10);
+
+/// Orphan shared memory segments are cleaned before our first process search.
+static CLEANUP: Once = Once::new();
 
 #[derive(Copy, Clone, Debug)]
 /// All possible strategies for iterative deepening.
@@ -20,10 +37,29 @@ pub enum IterativeStrategy {
     AlphaBeta,
 }
 
-/// Anytime algorithms strategies. Implemented in another process.
+impl IterativeStrategy {
+    /// Deepen iteratively until the context stops us, reporting each completed iteration.
+    fn deepen<R: FnMut(Option<Movement>)>(
+        self,
+        state: &Configuration,
+        context: &mut SearchContext,
+        report: R,
+    ) -> Option<Movement> {
+        match self {
+            IterativeStrategy::MinMax => min_max_deepening(state, context, report),
+            IterativeStrategy::AlphaBeta => alpha_beta_deepening(state, context, report),
+        }
+    }
+}
+
+/// Anytime algorithms strategies.
+/// By default the search runs on a worker thread which is stopped when time is up.
+/// It can also be run in another process (see `blobwar_iterative_deepening`) which is killed.
 pub struct IterativeDeepening {
     strategy: IterativeStrategy,
     duration: u64,
+    threaded: bool,
+    table: TranspositionTable,
 }
 
 impl fmt::Display for IterativeDeepening {
@@ -40,41 +76,168 @@ impl Strategy for IterativeDeepening {
     fn compute_next_move(
         &mut self,
         state: &Configuration,
-        _memo: Option<&mut HashMap<String, (i8, Movement)>>,
+        context: &mut SearchContext,
     ) -> Option<Movement> {
+        let duration = Duration::from_millis(self.duration);
+        let duration = context.limits.time.map_or(duration, |limit| min(limit, duration));
+        if self.threaded {
+            self.search_in_thread(state, duration, context)
+        } else {
+            self.search_in_process(state, duration)
+        }
+    }
+}
+
+impl IterativeDeepening {
+    /// New iterative deepening strategy with given algorithm.
+    /// default time is 1 second.
+    pub fn new(strategy: IterativeStrategy) -> IterativeDeepening {
+        IterativeDeepening {
+            strategy,
+            duration: 1000,
+            threaded: true,
+            table: Default::default(),
+        }
+    }
+
+    /// Sets duration in milliseconds on given algorithm.
+    pub fn duration(self, duration: u64) -> Self {
+        IterativeDeepening { duration, ..self }
+    }
+
+    /// Run the search on a worker thread (default) or in another process.
+    pub fn threaded(self, threaded: bool) -> Self {
+        IterativeDeepening { threaded, ..self }
+    }
+
+    /// Deepen on a worker thread and return the best move of the last completed iteration when
+    /// time is up.
+    /// The worker searches within the context's other limits and also stops when the context's
+    /// stop flag (if any) is raised.
+    /// The transposition table is kept from one move to the next.
+    fn search_in_thread(
+        &self,
+        state: &Configuration,
+        duration: Duration,
+        context: &mut SearchContext,
+    ) -> Option<Movement> {
+        let stop = AtomicBool::new(false);
+        let outer_stop = context.stop_flag();
+        let best_move = Mutex::new(None);
+        let (finished, wait_finished) = channel();
+        let table = &self.table;
+        let strategy = self.strategy;
+        // we stop the worker ourselves when time is up
+        let limits = SearchLimits {
+            time: None,
+            ..context.limits
+        };
+        table.new_search();
+        let stats = scope(|scope| {
+            let worker = scope.spawn(|| {
+                let mut context = SearchContext::new(limits)
+                    .with_table(table)
+                    .with_stop(&stop);
+                let last_move = strategy.deepen(state, &mut context, |movement| {
+                    *best_move.lock().unwrap() = movement
+                });
+                *best_move.lock().unwrap() = last_move;
+                // main thread might be gone already if we finished just in time
+                finished.send(()).ok();
+                context.stats()
+            });
+            // the search may also end before the deadline (nothing more to explore)
+            let deadline = Instant::now() + duration;
+            loop {
+                let now = Instant::now();
+                let outer_stopped = outer_stop.is_some_and(|outer| outer.load(Ordering::Relaxed));
+                if now >= deadline || outer_stopped {
+                    break;
+                }
+                let wait = min(deadline - now, STOP_CHECK_PERIOD);
+                if wait_finished.recv_timeout(wait) != Err(RecvTimeoutError::Timeout) {
+                    break;
+                }
+            }
+            stop.store(true, Ordering::Relaxed);
+            worker.join().expect("worker thread panicked")
+        });
+        context.add_stats(stats);
+        best_move.into_inner().unwrap()
+    }
+
+    /// Deepen in another process, killed when time is up.
+    /// The binary is looked for next to the current executable and communicates with us through
+    /// a shared memory segment whose name is given as third argument.
+    fn search_in_process(&self, state: &Configuration, duration: Duration) -> Option<Movement> {
+        CLEANUP.call_once(|| {
+            if let Err(e) = remove_orphan_segments() {
+                eprintln!("failed removing orphan shared memory segments: {}", e);
+            }
+        });
         let movement = AtomicMove::new().expect("failed creating shmem");
         let configuration = state.serialize();
-        let mut child = Command::new("blobwar_iterative_deepening")
-            .current_dir("./target/release/")
+        let binary = env::current_exe()
+            .expect("failed locating current executable")
+            .with_file_name(ANYTIME_BINARY);
+        let mut child = Command::new(binary)
             .arg(configuration)
             .arg((self.strategy as usize).to_string())
+            .arg(movement.name())
             .spawn()
             .expect("failed to start child process");
-        sleep(Duration::from_millis(self.duration));
+        sleep(duration);
         if let Err(e) = child.kill() {
             if e.kind() != io::ErrorKind::InvalidInput {
                 panic!("failed to kill child")
             }
         }
-        movement.load()
+        child.wait().expect("failed to wait for child");
+        let chosen_movement = movement.load();
+        movement.close().expect("failed releasing shmem");
+        chosen_movement
     }
 }
 
-impl IterativeDeepening {
-    /// New iterative deepening strategy with given algorithm.
-    /// default time is 2 seconds.
-    pub fn new(strategy: IterativeStrategy) -> IterativeDeepening {
-        IterativeDeepening {
-            strategy,
-            duration: 1000,
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+
+    #[test]
+    fn threaded_search_stops_on_time() {
+        let board = Board::default();
+        let state = Configuration::new(&board);
+        let mut strategy = IterativeDeepening::new(IterativeStrategy::AlphaBeta).duration(100);
+        let start = Instant::now();
+        let movement = strategy.compute_next_move(&state, &mut Default::default());
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert!(state.check_move(&movement.unwrap()));
     }
 
-    /// Sets duration in milliseconds on given algorithm.
-    pub fn duration(&self, duration: u64) -> Self {
-        IterativeDeepening {
-            strategy: self.strategy,
-            duration,
-        }
+    #[test]
+    fn threaded_search_follows_the_context() {
+        let board = Board::default();
+        let state = Configuration::new(&board);
+        let mut strategy = IterativeDeepening::new(IterativeStrategy::AlphaBeta).duration(10_000);
+        let start = Instant::now();
+        let mut context = SearchContext::new(SearchLimits::default().nodes(2000));
+        let movement = strategy.compute_next_move(&state, &mut context);
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(state.check_move(&movement.unwrap()));
+        assert!(context.nodes() > 2000);
+
+        let stop = AtomicBool::new(false);
+        let mut context = SearchContext::default().with_stop(&stop);
+        let start = Instant::now();
+        let movement = scope(|scope| {
+            scope.spawn(|| {
+                sleep(Duration::from_millis(50));
+                stop.store(true, Ordering::Relaxed)
+            });
+            strategy.compute_next_move(&state, &mut context)
+        });
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(state.check_move(&movement.unwrap()));
     }
 }
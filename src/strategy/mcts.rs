@@ -0,0 +1,364 @@
+//! Monte Carlo tree search.
+//! Instead of exploring all moves up to a given depth we play many (partly random) games to the
+//! end and grow a tree towards the most promising moves (UCT selection).
+use std::cmp::min;
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use super::context::SearchContext;
+use super::Strategy;
+use crate::configuration::{Configuration, Movement};
+use crate::positions::Positions;
+
+/// Stop growing the tree beyond that many nodes (playouts go on).
+const MAX_NODES: usize = 1 << 20;
+
+/// Playouts longer than that are stopped and judged on material.
+const MAX_PLAYOUT_LENGTH: usize = 256;
+
+/// Number of random candidates amongst which greedy playouts pick the best move.
+const GREEDY_CANDIDATES: usize = 4;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+/// How moves are chosen during playouts.
+pub enum Playout {
+    /// Uniformly random moves.
+    Random,
+    /// Best move (most conversions, duplicates first) amongst a few random ones.
+    Greedy,
+}
+
+/// Node of the search tree. `None` moves are passes.
+#[derive(Clone)]
+struct Node {
+    movement: Option<Movement>,
+    hash: u64,
+    children: Vec<usize>,
+    /// Moves not expanded yet (computed on first visit).
+    untried: Option<Vec<Option<Movement>>>,
+    visits: u32,
+    /// Sum of rewards for the player who played `movement`.
+    wins: f32,
+}
+
+impl Node {
+    fn new(movement: Option<Movement>, hash: u64) -> Self {
+        Node {
+            movement,
+            hash,
+            children: Vec::new(),
+            untried: None,
+            visits: 0,
+            wins: 0.0,
+        }
+    }
+}
+
+/// Small xorshift generator : good enough for playouts and reproducible.
+/// The state must not be 0.
+pub(crate) struct Random(pub(crate) u64);
+
+impl Random {
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub(crate) fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+}
+
+/// Monte Carlo tree search with UCT selection.
+/// Searches until the iteration budget or the time budget is exhausted (whichever comes first).
+/// The tree is kept from one move to the next : if the new configuration was already explored
+/// (we played a move and the opponent answered) the search restarts from its subtree.
+pub struct Mcts {
+    iterations: Option<u64>,
+    duration: u64,
+    playout: Playout,
+    exploration: f32,
+    random: Random,
+    tree: Vec<Node>,
+    holes: Positions,
+}
+
+impl Default for Mcts {
+    fn default() -> Self {
+        Mcts::new()
+    }
+}
+
+impl fmt::Display for Mcts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MCTS ({:?} playouts, {}ms", self.playout, self.duration)?;
+        if let Some(iterations) = self.iterations {
+            write!(f, ", {} iterations", iterations)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl Mcts {
+    /// New strategy with random playouts, searching for 1 second.
+    pub fn new() -> Self {
+        Mcts {
+            iterations: None,
+            duration: 1000,
+            playout: Playout::Random,
+            exploration: 2f32.sqrt(),
+            random: Random(0x9e37_79b9_7f4a_7c15),
+            tree: Vec::new(),
+            holes: Default::default(),
+        }
+    }
+
+    /// Stop after given number of iterations (playouts).
+    pub fn iterations(self, iterations: u64) -> Self {
+        Mcts {
+            iterations: Some(iterations),
+            ..self
+        }
+    }
+
+    /// Sets duration in milliseconds.
+    pub fn duration(self, duration: u64) -> Self {
+        Mcts { duration, ..self }
+    }
+
+    /// Choose how playouts are played.
+    pub fn playout(self, playout: Playout) -> Self {
+        Mcts { playout, ..self }
+    }
+
+    /// Sets the UCT exploration constant (square root of 2 by default).
+    pub fn exploration(self, exploration: f32) -> Self {
+        Mcts {
+            exploration,
+            ..self
+        }
+    }
+
+    /// Seed the random generator used for expansions and playouts.
+    pub fn seed(self, seed: u64) -> Self {
+        // xorshift must not start at 0
+        Mcts {
+            random: Random(seed | 1),
+            ..self
+        }
+    }
+
+    /// Make the tree start at given configuration, keeping the corresponding subtree if the
+    /// configuration is the current root or one of its descendants on the next two plies.
+    fn reroot(&mut self, state: &Configuration) {
+        let holes = state.board().holes;
+        let mut found = None;
+        if !self.tree.is_empty() && holes == self.holes {
+            let mut queue = VecDeque::from(vec![(0, 0)]);
+            while let Some((index, depth)) = queue.pop_front() {
+                if self.tree[index].hash == state.hash() {
+                    found = Some(index);
+                    break;
+                }
+                if depth < 2 {
+                    queue.extend(self.tree[index].children.iter().map(|&c| (c, depth + 1)));
+                }
+            }
+        }
+        self.holes = holes;
+        self.tree = match found {
+            Some(root) => self.subtree(root),
+            None => vec![Node::new(None, state.hash())],
+        };
+    }
+
+    /// Copy of the subtree starting at given node.
+    fn subtree(&self, root: usize) -> Vec<Node> {
+        let mut tree: Vec<Node> = Vec::new();
+        let mut stack: Vec<(usize, Option<usize>)> = vec![(root, None)];
+        while let Some((old, parent)) = stack.pop() {
+            let index = tree.len();
+            let mut node = self.tree[old].clone();
+            node.children.clear();
+            tree.push(node);
+            if let Some(parent) = parent {
+                tree[parent].children.push(index);
+            }
+            stack.extend(self.tree[old].children.iter().map(|&c| (c, Some(index))));
+        }
+        tree
+    }
+
+    /// Child of given node maximizing the UCT formula.
+    fn select(&self, node: usize) -> usize {
+        let parent_visits = (self.tree[node].visits.max(1) as f32).ln();
+        let uct = |child: &Node| {
+            let visits = child.visits.max(1) as f32;
+            child.wins / visits + self.exploration * (parent_visits / visits).sqrt()
+        };
+        *self.tree[node]
+            .children
+            .iter()
+            .max_by(|&&a, &&b| uct(&self.tree[a]).total_cmp(&uct(&self.tree[b])))
+            .expect("no child to select")
+    }
+
+    /// One iteration : select a leaf, expand it, play a game from there and update statistics.
+    fn iterate(&mut self, root: &Configuration) {
+        let mut state = *root;
+        let mut path = vec![0];
+        let mut node = 0;
+        while !state.game_over() {
+            let full = self.tree.len() >= MAX_NODES;
+            let untried = self.tree[node].untried.get_or_insert_with(|| {
+                let movements: Vec<_> = state.movements().map(Some).collect();
+                if movements.is_empty() {
+                    vec![None]
+                } else {
+                    movements
+                }
+            });
+            if !untried.is_empty() && !full {
+                let movement = untried.swap_remove(self.random.below(untried.len()));
+                play(&mut state, movement);
+                let child = self.tree.len();
+                self.tree.push(Node::new(movement, state.hash()));
+                self.tree[node].children.push(child);
+                path.push(child);
+                break;
+            }
+            if self.tree[node].children.is_empty() {
+                break;
+            }
+            node = self.select(node);
+            play(&mut state, self.tree[node].movement);
+            path.push(node);
+        }
+        let winner = self.play_out(state);
+        // rewards alternate along the path, starting with the player who moved last
+        let mut player = !state.current_player;
+        for &index in path.iter().rev() {
+            let node = &mut self.tree[index];
+            node.visits += 1;
+            node.wins += match winner {
+                Some(winner) if winner == player => 1.0,
+                Some(_) => 0.0,
+                None => 0.5,
+            };
+            player = !player;
+        }
+    }
+
+    /// Play given configuration until the end and return the winner (`None` for a draw).
+    fn play_out(&mut self, mut state: Configuration) -> Option<bool> {
+        let mut movements = Vec::new();
+        for _ in 0..MAX_PLAYOUT_LENGTH {
+            if state.game_over() {
+                break;
+            }
+            movements.clear();
+            movements.extend(state.movements());
+            if movements.is_empty() {
+                state.apply_skip();
+                continue;
+            }
+            let movement = match self.playout {
+                Playout::Random => movements[self.random.below(movements.len())],
+                Playout::Greedy => (0..GREEDY_CANDIDATES)
+                    .map(|_| movements[self.random.below(movements.len())])
+                    .max_by_key(|movement| {
+                        let duplicate = matches!(movement, Movement::Duplicate(_));
+                        (state.converted_blobs(movement), duplicate)
+                    })
+                    .unwrap(),
+            };
+            state.apply_movement(&movement);
+        }
+        // value is from the point of view of the player who just moved
+        match state.value() {
+            v if v > 0 => Some(!state.current_player),
+            v if v < 0 => Some(state.current_player),
+            _ => None,
+        }
+    }
+}
+
+fn play(state: &mut Configuration, movement: Option<Movement>) {
+    match movement {
+        Some(movement) => state.apply_movement(&movement),
+        None => state.apply_skip(),
+    };
+}
+
+impl Strategy for Mcts {
+    fn compute_next_move(
+        &mut self,
+        state: &Configuration,
+        context: &mut SearchContext,
+    ) -> Option<Movement> {
+        let duration = Duration::from_millis(self.duration);
+        let duration = context.limits.time.map_or(duration, |limit| min(limit, duration));
+        let start = Instant::now();
+        self.reroot(state);
+        let mut iterations = 0;
+        while self.iterations.is_none_or(|limit| iterations < limit)
+            && start.elapsed() < duration
+            && !context.visit()
+        {
+            self.iterate(state);
+            iterations += 1;
+        }
+        self.tree[0]
+            .children
+            .iter()
+            .map(|&child| &self.tree[child])
+            .max_by_key(|child| child.visits)
+            .and_then(|child| child.movement)
+            .or_else(|| state.movements().next())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+    use crate::fixtures::last_blue_blob;
+
+    #[test]
+    fn finds_winning_move() {
+        let board = Board::default();
+        let state = last_blue_blob(&board);
+        for playout in [Playout::Random, Playout::Greedy] {
+            let mut mcts = Mcts::new().iterations(2000).playout(playout);
+            let movement = mcts.compute_next_move(&state, &mut Default::default());
+            assert!(state.play(&movement.unwrap()).game_over());
+        }
+    }
+
+    #[test]
+    fn tree_is_reused() {
+        let board = Board::default();
+        let mut state = Configuration::new(&board);
+        let mut mcts = Mcts::new().iterations(500);
+        let movement = mcts.compute_next_move(&state, &mut Default::default()).unwrap();
+        state.apply_movement(&movement);
+        let answer = mcts.tree[0]
+            .children
+            .iter()
+            .map(|&child| &mcts.tree[child])
+            .find(|child| child.movement == Some(movement))
+            .and_then(|child| child.children.first().map(|&c| mcts.tree[c].movement))
+            .unwrap();
+        play(&mut state, answer);
+        mcts.reroot(&state);
+        assert!(mcts.tree[0].visits > 0);
+        assert_eq!(mcts.tree[0].hash, state.hash());
+        // another board : start from scratch
+        let other = Board::new(Positions::single(27));
+        mcts.reroot(&Configuration::new(&other));
+        assert_eq!(mcts.tree.len(), 1);
+    }
+}
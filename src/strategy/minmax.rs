@@ -1,25 +1,29 @@
 //! Implementation of the min max algorithm.
+use super::context::{deepen, SearchContext};
 use super::Strategy;
 use crate::configuration::{Configuration, Movement};
+use crate::evaluation::{Evaluator, Material};
+use crate::score::Score;
 use crate::shmem::AtomicMove;
 use std::fmt;
-use rayon::prelude::*;
-use std::collections::HashMap;
-// use std::time::{Duration, Instant};
-// use lazy_static::lazy_static;
 
-/// Min-Max algorithm with a given recursion depth.
-pub struct MinMax(pub u8);
+/// Min-Max algorithm with a given recursion depth, evaluating leaves with given `Evaluator`.
+pub struct MinMax<E = Material>(pub u8, pub E);
 
 // Given the evaluation function state.value this algo is more a negMax
 // than min_max, the eval fuction negates its self at every recursive call
 // so we calculate the max and we return the negation of the result to the
 // parent node
-fn min_max(depth: u8, state: &Configuration) -> (i8, Option<Movement>) {
-    if depth == 0 || !state.can_move() {
+#[allow(dead_code)]
+fn min_max(depth: u8, state: &Configuration) -> (i16, Option<Movement>) {
+    if depth == 0 || state.game_over() {
         return (state.value(), None);
     }
-    let mut best_score = i8::MIN;
+    if !state.can_move() {
+        let (score, _) = min_max(depth - 1, &state.skip_play());
+        return (-score, None);
+    }
+    let mut best_score = i16::MIN;
     let mut best_move: Option<Movement> = None;
     for movement in state.movements() {
         let next_conf: Configuration = state.play(&movement);
@@ -29,20 +33,24 @@ fn min_max(depth: u8, state: &Configuration) -> (i8, Option<Movement>) {
             best_move = Some(movement);
         }
     }
-    assert_ne!(best_score, i8::MIN); // maybe get rid of this for performance
+    assert_ne!(best_score, i16::MIN); // maybe get rid of this for performance
     (-best_score, best_move)
 }
 
-
-fn min_max_with_avg(depth: u8, state: &Configuration, with_avg: bool) -> (i8, f32, Option<Movement>) {
+#[allow(dead_code)]
+fn min_max_with_avg(
+    depth: u8,
+    state: &Configuration,
+    with_avg: bool,
+) -> (i16, f32, Option<Movement>) {
     if depth == 0 || !state.can_move() {
         return (state.value(), state.value().into(), None);
     }
-    let mut best_score = i8::MIN;
+    let mut best_score = i16::MIN;
     let mut best_avg = f32::MIN;
     let mut best_move: Option<Movement> = None;
     let mut count: i16 = 0;
-    let mut sum: i16 = 0;
+    let mut sum: i32 = 0;
     for movement in state.movements() {
         let next_conf: Configuration = state.play(&movement);
         let (score, avg, _) = min_max_with_avg(depth - 1, &next_conf, !with_avg);
@@ -52,155 +60,141 @@ fn min_max_with_avg(depth: u8, state: &Configuration, with_avg: bool) -> (i8, f3
             best_move = Some(movement);
         }
         count += 1;
-        sum += score as i16;
+        sum += i32::from(score);
     }
-    assert_ne!(best_score, i8::MIN); // maybe get rid of this for performance
-    (-best_score, (-sum/count).into(), best_move)
+    assert_ne!(best_score, i16::MIN); // maybe get rid of this for performance
+    (-best_score, (-sum / i32::from(count)) as f32, best_move)
 }
 
-
 // neg_max but in functional programming
-fn neg_max(depth: u8, state: &Configuration) -> (i8, Option<Movement>) {
+#[allow(dead_code)]
+fn neg_max(depth: u8, state: &Configuration) -> (i16, Option<Movement>) {
     if depth == 0 || !state.can_move() {
         return (state.value(), None);
     }
     let (bmove, (score, _)) = state
         .movements()
         .map(|movement| (movement, neg_max(depth - 1, &(state.play(&movement)))))
-        .max_by_key(|&(m, (value, _))| value)
-        .unwrap();
-    (-score, Some(bmove))
-}
-
-
-// parallel neg_max
-fn pneg_max(depth: u8, state: &Configuration) -> (i8, Option<Movement>) {
-    if depth == 0 || !state.can_move() {
-        return (state.value(), None);
-    }
-    let (bmove, (score, _)) = state
-        .movements()
-        // .par_bridge()
-        .collect::<Vec<_>>()
-        .into_par_iter()
-        .map(|movement| (movement, pneg_max(depth - 1, &(state.play(&movement)))))
         .max_by_key(|&(_, (value, _))| value)
         .unwrap();
     (-score, Some(bmove))
 }
 
 //expectimax in functional programming, hard make parallel
-fn pexpectimax(depth: u8, state: &Configuration, with_avg: bool) -> (i8, f32, Option<Movement>) {
-    if depth == 0 || !state.can_move() {
-        return (state.value(), state.value().into(), None);
+// if the context stops us, the returned scores are meaningless.
+fn pexpectimax<E: Evaluator + ?Sized>(
+    depth: u8,
+    ply: usize,
+    state: &Configuration,
+    with_avg: bool,
+    evaluator: &E,
+    context: &mut SearchContext,
+) -> (Score, f32, Option<Movement>) {
+    if context.visit() {
+        return (Score::DRAW, 0.0, None);
+    }
+    if let Some(score) = Score::terminal(state, ply) {
+        return (score, score.value().into(), None);
+    }
+    if depth == 0 {
+        let score = evaluator.evaluate(state);
+        return (score, score.value().into(), None);
+    }
+    if !state.can_move() {
+        // we pass and the opponent plays again
+        let (score, avg, _) = pexpectimax(
+            depth - 1,
+            ply + 1,
+            &state.skip_play(),
+            !with_avg,
+            evaluator,
+            context,
+        );
+        return (-score, -avg, None);
     }
 
     let (bmove, score, _bavg, sum, count) = state
         .movements()
-        .map(|movement| (movement, pexpectimax(depth - 1, &(state.play(&movement)), !with_avg)))
-        .fold((None, i8::MIN, f32::MIN, 0i16, 0i16), |acc, (mov,(score, avg, _))| {
-            let (mut bmove, mut max, mut bavg, sum, count) = acc;
-            if score > max || (with_avg && score == max && avg > bavg) {
-                max = score;
-                bmove = Some(mov);
-                bavg = avg;
-            }
-            (bmove, max, bavg, sum+score as i16, count+1)
-        });
-    (-score, (-sum/count).into(), bmove)
+        .map(|movement| {
+            (
+                movement,
+                pexpectimax(
+                    depth - 1,
+                    ply + 1,
+                    &(state.play(&movement)),
+                    !with_avg,
+                    evaluator,
+                    context,
+                ),
+            )
+        })
+        .fold(
+            (None, Score::MIN, f32::MIN, 0i32, 0i32),
+            |acc, (mov, (score, avg, _))| {
+                let (mut bmove, mut max, mut bavg, sum, count) = acc;
+                if score > max || (with_avg && score == max && avg > bavg) {
+                    max = score;
+                    bmove = Some(mov);
+                    bavg = avg;
+                }
+                (bmove, max, bavg, sum + i32::from(score.value()), count + 1)
+            },
+        );
+    (-score, (-sum / count) as f32, bmove)
 }
 
-// static mut min_maxs: Duration = Duration::from_secs(0);
-// static mut min_max_with_avgs: Duration = Duration::from_secs(0);
-// static mut neg_maxs: Duration = Duration::from_secs(0);
-// static mut pneg_maxs: Duration = Duration::from_secs(0);
-// static mut count1: u32 = 0;
-
-// static mut gvec: Vec<f32> = Vec::new();
-
-impl Strategy for MinMax {
+impl<E: Evaluator> Strategy for MinMax<E> {
     fn compute_next_move(
         &mut self,
         state: &Configuration,
-        _memo: Option<&mut HashMap<String, (i8, Movement)>>,
+        context: &mut SearchContext,
     ) -> Option<Movement> {
-        // let start_time = Instant::now();
-        // let (s0, mv0) = min_max(self.0, state);
-        // let end_time = Instant::now();
-        // unsafe {
-        //     count1 += 1;
-        //     min_maxs += end_time.duration_since(start_time);
-        //     println!("avg min_max {:?}", min_maxs/count1);
-        // }
-
-        // let start_time = Instant::now();
-        // let (s1, _, mv1) = pexpectimax(self.0, state, true);
-        // let end_time = Instant::now();
-        // let duration = end_time.duration_since(start_time);
-        // // if duration.as_secs_f32() > 1.0 {
-        // //     unreachable!("Duration was greater than 1 second");
-        // // }
-        // println!("time: {duration:?}");
-        // mv1
-
-        // let start_time = Instant::now();
-        // let (s2, mv2) = neg_max(self.0, state);
-        // let end_time = Instant::now();
-        // unsafe {
-        //     neg_maxs += end_time.duration_since(start_time);
-        //     println!("avg neg_max {:?}", neg_maxs/count1);
-        // }
-
-        // let start_time = Instant::now();
-        // let (s2, mv3) = pneg_max(self.0, state);
-        // let end_time = Instant::now();
-        // unsafe {
-        //     pneg_maxs += end_time.duration_since(start_time);
-        //     println!("avg normal func {:?}", pneg_maxs/count1);
-        // }
-        // println!("equality {}", mv0 == mv1);
-        // println!("equality {}", mv1 == mv2);
-        // println!("equality {}", mv2 == mv3);
-        // assert!(s0 == s1 && s1 == s2);
-        // let mv = if state.current_player {
-        //     // blue_player
-        // let (_, mv) = negascout(self.0, state, i8::MIN + 1, i8::MAX, memo);
-        //     mv
-        // } else {
-        //     // red player
-        //     let (_, mv) = alpha_beta_sorted(self.0, state, i8::MIN + 1, i8::MAX);
-        //     mv
-        // };
-        // let start_time = Instant::now();
-        // let (_s1, mv1) = pneg_max(self.0, state);
-        // let end_time = Instant::now();
-        // let duration = end_time.duration_since(start_time);
-        // if duration.as_secs_f32() > 1.0 {
-        //     unreachable!("Duration was greater than 1 second");
-        // }
-        // unsafe {
-        //     gvec.push(duration.as_secs_f32());
-        //     println!(">{:?}", gvec);
-        // }
-        // mv1
-        let (s1, _, mv1) = pexpectimax(self.0, state, true);
-        mv1
+        let depth = context.depth(self.0);
+        if !context.interruptible() {
+            let (_, _, movement) = pexpectimax(depth, 0, state, true, &self.1, context);
+            return movement;
+        }
+        deepen(
+            state,
+            context,
+            depth,
+            |depth, context| pexpectimax(depth, 0, state, true, &self.1, context).2,
+            |_| {},
+        )
     }
 }
 
-impl fmt::Display for MinMax {
+impl<E: Evaluator> fmt::Display for MinMax<E> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Min - Max (max level: {})", self.0)
+        write!(f, "Min - Max (max level: {}, {})", self.0, self.1)
     }
 }
 
 /// Anytime min max algorithm.
-/// Any time algorithms will compute until a deadline is hit and the process is killed.
-/// They are therefore run in another process and communicate through shared memory.
-/// This function is intended to be called from blobwar_iterative_deepening.
-pub fn min_max_anytime(state: &Configuration) {
-    let mut movement = AtomicMove::connect().expect("failed connecting to shmem");
-    for depth in 1..100 {
-        movement.store(MinMax(depth).compute_next_move(state, None));
-    }
+/// Any time algorithms compute until a deadline is hit. `IterativeDeepening` normally runs them
+/// on a worker thread which it stops, but can also run them in another process which it kills,
+/// communicating through shared memory.
+/// This function is intended to be called in that process (`blobwar_iterative_deepening`),
+/// with the name of the shared memory segment created by the master process.
+pub fn min_max_anytime(state: &Configuration, segment: &str) {
+    let mut movement = AtomicMove::connect(segment).expect("failed connecting to shmem");
+    min_max_deepening(state, &mut Default::default(), |chosen_movement| {
+        movement.store(chosen_movement)
+    });
+}
+
+/// Iterative deepening min max, until the context stops us or up to its depth limit.
+/// `report` is called with the best move of each completed iteration.
+pub(crate) fn min_max_deepening<R: FnMut(Option<Movement>)>(
+    state: &Configuration,
+    context: &mut SearchContext,
+    report: R,
+) -> Option<Movement> {
+    deepen(
+        state,
+        context,
+        context.depth(100),
+        |depth, context| pexpectimax(depth, 0, state, true, &Material, context).2,
+        report,
+    )
 }
@@ -1,19 +1,85 @@
 //! We provide here structs for all possible kinds of players and AI.
 use crate::configuration::{Configuration, Movement};
+use crate::record::{ForfeitReason, GameResult};
 use std::fmt;
-use std::collections::HashMap;
 
 /// To be a strategy you need to be able to compute the next move.
 pub trait Strategy: fmt::Display {
     /// Take current `Configuration` and return what to do next.
     /// None if no move is possible.
+    /// The `SearchContext` gives limits to respect and resources shared between searches ;
+    /// strategies use what they need from it.
     fn compute_next_move(
         &mut self,
         state: &Configuration,
-        memo: Option<&mut HashMap<String, (i8, Movement)>>,
+        context: &mut SearchContext,
     ) -> Option<Movement>;
+
+    /// Like `compute_next_move` but interactive players may also ask to take back their last
+    /// move. By default we just compute the next move.
+    fn decide(&mut self, state: &Configuration, context: &mut SearchContext) -> Decision {
+        Decision::Play(self.compute_next_move(state, context))
+    }
+
+    /// Called once the game is over, with the final blob counts. Does nothing by default.
+    fn game_over(&mut self, _result: &GameResult) {}
+}
+
+/// Boxed strategies, for choosing them at runtime.
+impl<S: Strategy + ?Sized> Strategy for Box<S> {
+    fn compute_next_move(
+        &mut self,
+        state: &Configuration,
+        context: &mut SearchContext,
+    ) -> Option<Movement> {
+        (**self).compute_next_move(state, context)
+    }
+
+    fn decide(&mut self, state: &Configuration, context: &mut SearchContext) -> Decision {
+        (**self).decide(state, context)
+    }
+
+    fn game_over(&mut self, result: &GameResult) {
+        (**self).game_over(result)
+    }
+}
+
+/// Borrowed strategies, for keeping players from one game to the next.
+impl<S: Strategy + ?Sized> Strategy for &mut S {
+    fn compute_next_move(
+        &mut self,
+        state: &Configuration,
+        context: &mut SearchContext,
+    ) -> Option<Movement> {
+        (**self).compute_next_move(state, context)
+    }
+
+    fn decide(&mut self, state: &Configuration, context: &mut SearchContext) -> Decision {
+        (**self).decide(state, context)
+    }
+
+    fn game_over(&mut self, result: &GameResult) {
+        (**self).game_over(result)
+    }
+}
+
+/// What a player decided to do on its turn.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Decision {
+    /// Play given move (skip turn if `None`).
+    Play(Option<Movement>),
+    /// Take back our last move (and the answer to it) to play again.
+    TakeBack,
+    /// Give up the game (when a remote player cannot play anymore for instance).
+    Forfeit(ForfeitReason),
 }
 
+pub mod tt;
+pub use self::tt::TranspositionTable;
+pub mod context;
+pub use self::context::{SearchContext, SearchLimits, SearchStats};
+pub mod ordering;
+pub use self::ordering::MoveOrdering;
 pub mod human;
 pub use self::human::Human;
 pub mod network;
@@ -24,6 +90,16 @@ pub mod minmax;
 pub use self::minmax::{min_max_anytime, MinMax};
 pub mod alphabeta;
 pub use self::alphabeta::{alpha_beta_anytime, AlphaBeta};
+pub mod parallel;
+pub use self::parallel::ParallelAlphaBeta;
+pub mod book;
+pub use self::book::{Book, BookStrategy};
+pub mod endgame;
+pub mod negascout;
+pub use self::negascout::NegaScout;
+pub mod mcts;
+pub use self::mcts::{Mcts, Playout};
 pub mod iterative;
 pub use self::iterative::IterativeDeepening;
 pub use self::iterative::IterativeStrategy;
+pub mod spec;
@@ -0,0 +1,235 @@
+//! Principal variation search (NegaScout).
+use std::fmt;
+
+use super::context::{deepen, SearchContext};
+use super::tt::Lookup;
+use super::Strategy;
+use crate::configuration::{Configuration, Movement};
+use crate::evaluation::{Evaluator, Material};
+use crate::score::Score;
+
+/// NegaScout (principal variation search) with given maximum number of recursions.
+/// Only the first move of each node is searched with the full window : other moves are tested
+/// with a null window (are they better than what we have ?) and re-searched only if they are.
+/// This pays off when the first move is often the best one, so it works best with a
+/// transposition table (in the context) providing the best move of previous iterations.
+/// Leaves are evaluated with given `Evaluator`.
+pub struct NegaScout<E = Material>(pub u8, pub E);
+
+impl<E: Evaluator> fmt::Display for NegaScout<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NegaScout (max level: {}, {})", self.0, self.1)
+    }
+}
+
+/// Principal variation search. Like `alpha_beta` the returned score is from the point of view
+/// of the player who just moved, and is meaningless if the context stops us.
+pub(crate) fn negascout<E: Evaluator + ?Sized>(
+    depth: u8,
+    ply: usize,
+    state: &Configuration,
+    mut alpha: Score,
+    mut beta: Score,
+    evaluator: &E,
+    context: &mut SearchContext,
+) -> (Score, Option<Movement>) {
+    if context.visit() {
+        return (Score::DRAW, None);
+    }
+    if let Some(score) = Score::terminal(state, ply) {
+        return (score, None);
+    }
+    if depth == 0 {
+        return (evaluator.evaluate(state), None);
+    }
+    if !state.can_move() {
+        // we have to pass, the opponent plays again (he can, or the game would be over)
+        let (score, _) = negascout(
+            depth - 1,
+            ply + 1,
+            &state.skip_play(),
+            -beta,
+            -alpha,
+            evaluator,
+            context,
+        );
+        return (-score, None);
+    }
+    let table = context.table;
+    let lookup = table.map(|table| table.lookup(state, depth, ply, &mut alpha, &mut beta));
+    let hash_move = match lookup {
+        Some(Lookup::Cutoff(score, movement)) => return (-score, movement),
+        Some(Lookup::Search(hash_move)) => hash_move,
+        None => None,
+    };
+    let window = (alpha, beta);
+    let mut best_score = Score::MIN;
+    let mut best_move: Option<Movement> = None;
+    let movements = context.ordering.order(state, ply, hash_move);
+    for (index, movement) in movements.into_iter().enumerate() {
+        let next_conf = state.play(&movement);
+        let score = if best_move.is_none() {
+            negascout(
+                depth - 1,
+                ply + 1,
+                &next_conf,
+                -beta,
+                -alpha,
+                evaluator,
+                context,
+            )
+            .0
+        } else {
+            let (score, _) = negascout(
+                depth - 1,
+                ply + 1,
+                &next_conf,
+                -alpha - 1,
+                -alpha,
+                evaluator,
+                context,
+            );
+            if score > alpha && score < beta {
+                // better than expected : we need the exact value
+                negascout(
+                    depth - 1,
+                    ply + 1,
+                    &next_conf,
+                    -beta,
+                    -alpha,
+                    evaluator,
+                    context,
+                )
+                .0
+            } else {
+                score
+            }
+        };
+        if context.stopped() {
+            return (Score::DRAW, best_move);
+        }
+        if score > best_score {
+            best_score = score;
+            best_move = Some(movement);
+            if best_score > alpha {
+                alpha = best_score;
+            }
+            if alpha >= beta {
+                context.cutoff(movement, index, ply, depth);
+                break;
+            }
+        }
+    }
+    if let Some(table) = table {
+        table.record(state, depth, ply, window, best_score, best_move);
+    }
+    (-best_score, best_move)
+}
+
+impl<E: Evaluator> Strategy for NegaScout<E> {
+    fn compute_next_move(
+        &mut self,
+        state: &Configuration,
+        context: &mut SearchContext,
+    ) -> Option<Movement> {
+        let depth = context.depth(self.0);
+        if !context.interruptible() {
+            let (_, movement) =
+                negascout(depth, 0, state, Score::MIN, Score::MAX, &self.1, context);
+            return movement;
+        }
+        deepen(
+            state,
+            context,
+            depth,
+            |depth, context| negascout(depth, 0, state, Score::MIN, Score::MAX, &self.1, context).1,
+            |_| {},
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+    use crate::strategy::alphabeta::alpha_beta;
+    use crate::strategy::TranspositionTable;
+
+    /// Positions reached by playing a few moves.
+    fn positions(board: &Board) -> Vec<Configuration<'_>> {
+        let mut state = Configuration::new(board);
+        let mut positions = Vec::new();
+        for turn in 0..12 {
+            positions.push(state);
+            let movement = state.movements().nth(turn * 5 % 11);
+            match movement {
+                Some(movement) => state.apply_movement(&movement),
+                None => state.apply_skip(),
+            };
+        }
+        positions
+    }
+
+    #[test]
+    fn same_scores_as_alpha_beta() {
+        let board = Board::default();
+        for state in positions(&board) {
+            for depth in 1..4 {
+                let mut context = SearchContext::default();
+                let (expected, _) = alpha_beta(
+                    depth,
+                    0,
+                    &state,
+                    Score::MIN,
+                    Score::MAX,
+                    &Material,
+                    &mut context,
+                );
+                let mut context = SearchContext::default();
+                let (score, _) = negascout(
+                    depth,
+                    0,
+                    &state,
+                    Score::MIN,
+                    Score::MAX,
+                    &Material,
+                    &mut context,
+                );
+                assert_eq!(expected, score);
+            }
+        }
+    }
+
+    type Search = (Score, Option<Movement>);
+
+    /// Nodes visited by iterative deepening up to depth 4 with a transposition table.
+    fn nodes<F>(state: &Configuration, search: F) -> u64
+    where
+        F: Fn(u8, usize, &Configuration, Score, Score, &Material, &mut SearchContext) -> Search,
+    {
+        let table = TranspositionTable::new(1);
+        let mut context = SearchContext::default().with_table(&table);
+        for depth in 1..5 {
+            search(
+                depth,
+                0,
+                state,
+                Score::MIN,
+                Score::MAX,
+                &Material,
+                &mut context,
+            );
+        }
+        context.nodes()
+    }
+
+    #[test]
+    fn visits_fewer_nodes() {
+        let board = Board::default();
+        let (alpha_beta_nodes, negascout_nodes) = positions(&board)
+            .iter()
+            .map(|state| (nodes(state, alpha_beta), nodes(state, negascout)))
+            .fold((0, 0), |(a, n), (sa, sn)| (a + sa, n + sn));
+        assert!(negascout_nodes < alpha_beta_nodes);
+    }
+}
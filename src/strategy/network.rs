@@ -1,18 +1,37 @@
 //! Network player (server side)
-use super::Strategy;
+use super::{Decision, Strategy};
+use crate::clock::GRACE;
 use crate::configuration::{Configuration, Movement};
+use crate::protocol::{Message, VERSION};
+use crate::record::{ForfeitReason, GameResult};
 
-use serde_json::{de, Deserializer, StreamDeserializer};
+use super::context::SearchContext;
 use std::fmt;
 use std::io::prelude::*;
-use std::net::TcpStream;
-use std::collections::HashMap;
+use std::io::{self, BufReader};
+use std::net::{Shutdown, TcpStream};
+use std::time::Duration;
+
+/// Time given to clients to answer our hello.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time given by default to clients to answer a position when moves have no time limit.
+pub const MOVE_TIMEOUT: Duration = Duration::from_secs(60);
 
 /// Let a remote client enter moves.
+/// Clients speak the message protocol of `protocol` unless created with `legacy`.
+/// Clients answering late, disconnecting or sending anything else than a legal move forfeit
+/// the game.
 pub struct NetworkPlayer {
     connection: TcpStream,
-    movements: StreamDeserializer<'static, de::IoRead<TcpStream>, Option<Movement>>,
+    reader: BufReader<TcpStream>,
     name: String,
+    legacy: bool,
+    colour_sent: bool,
+    /// Time to answer when moves have no time limit.
+    move_timeout: Duration,
+    /// Set once the client timed out or disconnected : we stop listening to it.
+    lost: bool,
 }
 
 impl fmt::Display for NetworkPlayer {
@@ -21,17 +40,126 @@ impl fmt::Display for NetworkPlayer {
     }
 }
 
+/// Error for answers which are not legal moves.
+fn illegal(reason: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason)
+}
+
 impl NetworkPlayer {
-    /// Create a new network player
-    pub fn new(data: TcpStream) -> Self {
-        let origin = data.peer_addr().unwrap().to_string();
-        let connection = data.try_clone().unwrap();
-        let movements = Deserializer::from_reader(data).into_iter::<Option<Movement>>();
-        NetworkPlayer {
+    /// Create a new network player, exchanging greetings with the client.
+    pub fn new(data: TcpStream) -> io::Result<Self> {
+        let mut player = NetworkPlayer::connect(data, false)?;
+        Message::Hello {
+            version: VERSION,
+            name: "blobwar server".to_owned(),
+        }
+        .send(&player.connection)?;
+        player
+            .connection
+            .set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        match Message::receive(&mut player.reader)? {
+            Message::Hello { version, name } if version == VERSION => {
+                player.name = format!("{} ({})", name, player.name);
+                Ok(player)
+            }
+            Message::Hello { version, .. } => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "client speaks protocol version {} instead of {}",
+                    version, VERSION
+                ),
+            )),
+            message => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("expected hello from client, got {:?}", message),
+            )),
+        }
+    }
+
+    /// Create a network player for a legacy client : it receives bare configurations and
+    /// answers json movements.
+    pub fn legacy(data: TcpStream) -> io::Result<Self> {
+        NetworkPlayer::connect(data, true)
+    }
+
+    fn connect(data: TcpStream, legacy: bool) -> io::Result<Self> {
+        let origin = data.peer_addr()?.to_string();
+        let connection = data.try_clone()?;
+        Ok(NetworkPlayer {
             connection,
-            movements,
+            reader: BufReader::new(data),
             name: origin,
+            legacy,
+            colour_sent: false,
+            move_timeout: MOVE_TIMEOUT,
+            lost: false,
+        })
+    }
+
+    /// Sets the time given to the client to answer when moves have no time limit
+    /// (`MOVE_TIMEOUT` by default).
+    pub fn move_timeout(mut self, move_timeout: Duration) -> Self {
+        // a zero timeout is not accepted by sockets
+        self.move_timeout = move_timeout.max(Duration::from_millis(1));
+        self
+    }
+
+    /// Send the configuration and read the answer of a legacy client.
+    fn legacy_move(&mut self, configuration: &Configuration) -> io::Result<Option<Movement>> {
+        let mut message = configuration.serialize();
+        message.push('\n');
+        self.connection.write_all(message.as_bytes())?;
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        // legacy clients number cells in an 8 x 8 grid
+        let movement: Option<Movement> =
+            serde_json::from_str(&line).map_err(|error| illegal(error.to_string()))?;
+        match movement.map(|legacy| (legacy, Movement::from_legacy(legacy))) {
+            None if configuration.can_move() => Err(illegal("cannot pass with moves left".into())),
+            None => Ok(None),
+            Some((_, Some(movement))) if configuration.check_move(&movement) => Ok(Some(movement)),
+            Some((_, Some(movement))) => Err(illegal(format!("{} is not allowed", movement))),
+            Some((legacy, None)) => Err(illegal(format!("{:?} is outside of the board", legacy))),
+        }
+    }
+
+    /// Send the position and read the answer of the client.
+    /// Anything else than a legal move is reported to the client as an illegal move.
+    fn next_move(
+        &mut self,
+        configuration: &Configuration,
+        context: &SearchContext,
+    ) -> io::Result<Option<Movement>> {
+        if !self.colour_sent {
+            Message::Colour {
+                player: configuration.current_player,
+            }
+            .send(&self.connection)?;
+            self.colour_sent = true;
+        }
+        Message::Position {
+            configuration: configuration.serialize(),
+            millis: context.limits.time.map(|time| time.as_millis() as u64),
         }
+        .send(&self.connection)?;
+        let reason = match Message::receive(&mut self.reader) {
+            Ok(Message::Pass) if !configuration.can_move() => return Ok(None),
+            Ok(Message::Pass) => "cannot pass with moves left".to_owned(),
+            Ok(Message::Move { movement }) if configuration.check_move(&movement) => {
+                return Ok(Some(movement))
+            }
+            Ok(Message::Move { movement }) => format!("{} is not allowed", movement),
+            Ok(message) => format!("expected a move, got {:?}", message),
+            Err(ref error) if error.kind() == io::ErrorKind::InvalidData => error.to_string(),
+            Err(error) => return Err(error),
+        };
+        Message::IllegalMove {
+            reason: reason.clone(),
+        }
+        .send(&self.connection)?;
+        Err(illegal(reason))
     }
 }
 
@@ -39,13 +167,254 @@ impl Strategy for NetworkPlayer {
     fn compute_next_move(
         &mut self,
         configuration: &Configuration,
-        _memo: Option<&mut HashMap<String, (i8, Movement)>>,
+        context: &mut SearchContext,
     ) -> Option<Movement> {
-        let mut message = configuration.serialize();
-        message.push('\n');
-        self.connection
-            .write_all(message.into_bytes().as_slice())
-            .expect("sending configuration remotely failed");
-        self.movements.next().unwrap().unwrap()
+        match self.decide(configuration, context) {
+            Decision::Play(movement) => movement,
+            _ => None,
+        }
+    }
+
+    fn decide(&mut self, configuration: &Configuration, context: &mut SearchContext) -> Decision {
+        if self.lost {
+            return Decision::Forfeit(ForfeitReason::Disconnected);
+        }
+        // the client has the time of its move (and some more for the network) to answer
+        let timeout = context
+            .limits
+            .time
+            .map_or(self.move_timeout, |time| time + GRACE);
+        let movement = self
+            .connection
+            .set_read_timeout(Some(timeout))
+            .and_then(|_| {
+                if self.legacy {
+                    self.legacy_move(configuration)
+                } else {
+                    self.next_move(configuration, context)
+                }
+            });
+        match movement {
+            Ok(movement) => Decision::Play(movement),
+            Err(error) => {
+                let reason = match error.kind() {
+                    io::ErrorKind::InvalidData => ForfeitReason::IllegalMove,
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => ForfeitReason::Timeout,
+                    _ => ForfeitReason::Disconnected,
+                };
+                // a late answer would be taken for the answer to the next position
+                self.lost = reason != ForfeitReason::IllegalMove;
+                Decision::Forfeit(reason)
+            }
+        }
+    }
+
+    fn game_over(&mut self, result: &GameResult) {
+        if !self.legacy {
+            // the client may already be gone : nothing to do about it
+            let _ = Message::Result {
+                red: result.red,
+                blue: result.blue,
+                forfeit: result.forfeit,
+            }
+            .send(&self.connection);
+            // colours may change in the next game
+            self.colour_sent = false;
+        }
+        if self.lost {
+            let _ = Message::Bye.send(&self.connection);
+            let _ = self.connection.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+impl Drop for NetworkPlayer {
+    fn drop(&mut self) {
+        if !self.legacy && !self.lost {
+            let _ = Message::Bye.send(&self.connection);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+    use crate::clock::TimeControl;
+    use crate::evaluation::Material;
+    use crate::record::{Forfeit, GameRecord};
+    use crate::strategy::Greedy;
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Client giving the answers computed by `answer` (staying silent on `None`) and returning
+    /// all received messages.
+    fn client<A>(stream: TcpStream, answer: A) -> Vec<Message>
+    where
+        A: Fn(&Configuration) -> Option<Message>,
+    {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut received = vec![Message::receive(&mut reader).unwrap()];
+        Message::Hello {
+            version: VERSION,
+            name: "test client".to_owned(),
+        }
+        .send(&stream)
+        .unwrap();
+        loop {
+            let message = Message::receive(&mut reader).unwrap();
+            received.push(message.clone());
+            match message {
+                Message::Position { configuration, .. } => {
+                    let board = Board::deserialize(&configuration).unwrap();
+                    let state = Configuration::deserialize(&configuration, &board).unwrap();
+                    if let Some(answer) = answer(&state) {
+                        answer.send(&stream).unwrap();
+                    }
+                }
+                Message::Bye => return received,
+                _ => {}
+            }
+        }
+    }
+
+    /// Play Greedy against a remote client, returning the record and the client messages.
+    fn remote_game<A>(time_control: TimeControl, answer: A) -> (GameRecord, Vec<Message>)
+    where
+        A: Fn(&Configuration) -> Option<Message> + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let remote = thread::spawn(move || client(TcpStream::connect(address).unwrap(), answer));
+        let player = NetworkPlayer::new(listener.accept().unwrap().0)
+            .unwrap()
+            .move_timeout(Duration::from_secs(1));
+        assert_eq!(
+            player.to_string().split(" (").next(),
+            Some("On network : test client")
+        );
+        let board = Board::with_size(8, 2, Default::default());
+        let mut state = Configuration::deserialize("0r      b/        ", &board).unwrap();
+        let record = state.battle_with_clock(time_control, Greedy(Material), player);
+        (record, remote.join().unwrap())
+    }
+
+    /// Play Greedy against a legacy client answering with `answer`, on the legacy 8 x 8 board.
+    fn legacy_game<A>(answer: A) -> GameRecord
+    where
+        A: Fn(&Configuration) -> Option<Movement> + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let remote = thread::spawn(move || {
+            let stream = TcpStream::connect(address).unwrap();
+            for line in BufReader::new(stream.try_clone().unwrap()).lines() {
+                // configurations come without row separators, like from baseline servers
+                let line = line.unwrap();
+                assert!(!line.contains('/'));
+                let board = Board::deserialize(&line).unwrap();
+                let state = Configuration::deserialize(&line, &board).unwrap();
+                let movement = answer(&state).map(|movement| movement.to_legacy().unwrap());
+                let mut answer = serde_json::to_string(&movement).unwrap();
+                answer.push('\n');
+                (&stream).write_all(answer.as_bytes()).unwrap();
+            }
+        });
+        let player = NetworkPlayer::legacy(listener.accept().unwrap().0).unwrap();
+        let mut board = Board::default();
+        board.rules.max_plies = Some(30);
+        let mut state = Configuration::new(&board);
+        let record = state.battle(Greedy(Material), player);
+        remote.join().unwrap();
+        record
+    }
+
+    #[test]
+    fn legacy_clients_play_with_eight_columns() {
+        let record = legacy_game(|state| state.movements().next());
+        let result = record.result.unwrap();
+        assert_eq!(result.forfeit, None);
+        assert!(record.moves.len() > 1);
+        assert_eq!(record.validate(), Ok(()));
+        // h8 is a starting blob
+        let record = legacy_game(|_| "h8".parse().ok());
+        let forfeit = record.result.unwrap().forfeit;
+        assert_eq!(
+            forfeit.map(|forfeit| (forfeit.player, forfeit.reason)),
+            Some((true, ForfeitReason::IllegalMove))
+        );
+    }
+
+    #[test]
+    fn remote_clients_play_with_messages() {
+        let (record, received) = remote_game(Default::default(), |state| {
+            Some(Message::play(state.movements().next()))
+        });
+        assert!(matches!(
+            received[0],
+            Message::Hello {
+                version: VERSION,
+                ..
+            }
+        ));
+        assert_eq!(received[1], Message::Colour { player: true });
+        let result = record.result.unwrap();
+        assert_eq!(result.forfeit, None);
+        assert_eq!(
+            received[received.len() - 2..],
+            [
+                Message::Result {
+                    red: result.red,
+                    blue: result.blue,
+                    forfeit: None
+                },
+                Message::Bye
+            ]
+        );
+        assert_eq!(record.validate(), Ok(()));
+    }
+
+    #[test]
+    fn illegal_or_late_answers_forfeit() {
+        let illegal = |_: &Configuration| Some(Message::play(Some(Movement::Duplicate(100))));
+        let pass = |_: &Configuration| Some(Message::Pass);
+        let silent = |_: &Configuration| None;
+        let time_control = TimeControl::default().per_move(Duration::from_millis(50));
+        for (reason, (record, received)) in [
+            (
+                ForfeitReason::IllegalMove,
+                remote_game(Default::default(), illegal),
+            ),
+            (
+                ForfeitReason::IllegalMove,
+                remote_game(Default::default(), pass),
+            ),
+            (ForfeitReason::Timeout, remote_game(time_control, silent)),
+            // without time limit clients still have to answer at some point
+            (
+                ForfeitReason::Timeout,
+                remote_game(Default::default(), silent),
+            ),
+        ] {
+            let forfeit = Some(Forfeit {
+                player: true,
+                reason,
+            });
+            let result = record.result.unwrap();
+            assert_eq!(result.forfeit, forfeit);
+            assert_eq!(result.winner(), Some(false));
+            assert_eq!(
+                received
+                    .iter()
+                    .any(|m| matches!(m, Message::IllegalMove { .. })),
+                reason == ForfeitReason::IllegalMove
+            );
+            assert!(received.contains(&Message::Result {
+                red: result.red,
+                blue: result.blue,
+                forfeit
+            }));
+            assert_eq!(record.validate(), Ok(()));
+        }
     }
 }
@@ -0,0 +1,157 @@
+//! Move ordering for the alpha - beta family.
+//! The sooner we search the best move, the more we prune. Moves are ranked by :
+//! - the best move stored in the transposition table (hash move)
+//! - the number of enemy blobs they convert
+//! - duplicates before jumps (we keep our starting blob)
+//! - killer moves (moves which caused a cutoff at the same ply in a sibling node)
+//! - history (how many cutoffs the move caused anywhere, weighted by depth)
+use crate::configuration::{Configuration, Movement};
+use crate::positions::CELLS;
+use std::cmp::{min, Reverse};
+
+/// Plies for which we keep killer moves.
+const MAX_PLY: usize = 128;
+
+/// One history counter for each (source, destination) pair (duplicates use destination twice).
+const HISTORY_SIZE: usize = CELLS * CELLS;
+
+// rank keys : each criterion dominates all the following ones
+const CAPTURE_WEIGHT: u32 = 1 << 24;
+const DUPLICATE_BONUS: u32 = 1 << 23;
+const KILLER_BONUS: u32 = 1 << 22;
+const MAX_HISTORY: u32 = KILLER_BONUS - 1;
+
+/// Killer moves and history table, learned during a search and kept between iterations.
+pub struct MoveOrdering {
+    enabled: bool,
+    killers: Vec<[Option<Movement>; 2]>,
+    history: Vec<u32>,
+}
+
+impl Default for MoveOrdering {
+    fn default() -> Self {
+        MoveOrdering {
+            enabled: true,
+            killers: vec![[None; 2]; MAX_PLY],
+            history: vec![0; HISTORY_SIZE],
+        }
+    }
+}
+
+fn history_index(movement: &Movement) -> usize {
+    match *movement {
+        Movement::Duplicate(destination) => destination as usize * (CELLS + 1),
+        Movement::Jump(source, destination) => source as usize * CELLS + destination as usize,
+    }
+}
+
+impl MoveOrdering {
+    /// No ordering at all (besides the hash move) : moves are searched in generation order.
+    /// Useful to measure how much ordering helps.
+    pub fn disabled() -> Self {
+        MoveOrdering {
+            enabled: false,
+            ..Default::default()
+        }
+    }
+
+    /// Return all moves for given configuration (searched at given ply), best candidates first.
+    pub fn order(
+        &self,
+        state: &Configuration,
+        ply: usize,
+        hash_move: Option<Movement>,
+    ) -> Vec<Movement> {
+        if !self.enabled {
+            return hash_move
+                .into_iter()
+                .chain(state.movements().filter(|m| Some(*m) != hash_move))
+                .collect();
+        }
+        let killers = self.killers.get(ply).copied().unwrap_or_default();
+        let mut ranked: Vec<(u32, Movement)> = state
+            .movements()
+            .map(|movement| {
+                let rank = if Some(movement) == hash_move {
+                    u32::MAX
+                } else {
+                    let duplicate = match movement {
+                        Movement::Duplicate(_) => DUPLICATE_BONUS,
+                        Movement::Jump(_, _) => 0,
+                    };
+                    let killer = if killers.contains(&Some(movement)) {
+                        KILLER_BONUS
+                    } else {
+                        0
+                    };
+                    state.converted_blobs(&movement) as u32 * CAPTURE_WEIGHT
+                        + duplicate
+                        + killer
+                        + self.history[history_index(&movement)]
+                };
+                (rank, movement)
+            })
+            .collect();
+        ranked.sort_by_key(|&(rank, _)| Reverse(rank));
+        ranked.into_iter().map(|(_, movement)| movement).collect()
+    }
+
+    /// Learn that given move caused a cutoff at given ply, with given remaining depth.
+    pub fn cutoff(&mut self, movement: Movement, ply: usize, depth: u8) {
+        if !self.enabled {
+            return;
+        }
+        if let Some(killers) = self.killers.get_mut(ply) {
+            if killers[0] != Some(movement) {
+                killers[1] = killers[0];
+                killers[0] = Some(movement);
+            }
+        }
+        let counter = &mut self.history[history_index(&movement)];
+        *counter = min(MAX_HISTORY, *counter + u32::from(depth) * u32::from(depth));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+    use crate::fixtures::last_blue_blob;
+
+    #[test]
+    fn history_slots_are_distinct() {
+        use crate::positions::{BoardPosition, Position};
+        use std::collections::HashSet;
+        let cells = 0..CELLS as Position;
+        let duplicates = cells.clone().map(Movement::Duplicate);
+        let jumps = cells.clone().flat_map(|source| {
+            cells
+                .clone()
+                .filter(move |&destination| source.distance_to(destination) == 2)
+                .map(move |destination| Movement::Jump(source, destination))
+        });
+        let mut slots = HashSet::new();
+        for movement in duplicates.chain(jumps) {
+            let slot = history_index(&movement);
+            assert!(slot < HISTORY_SIZE);
+            assert!(slots.insert(slot), "{:?} shares its slot", movement);
+        }
+    }
+
+    #[test]
+    fn hash_move_then_captures_first() {
+        let board = Board::default();
+        let state = last_blue_blob(&board);
+        let ordering = MoveOrdering::default();
+        let hash_move = Movement::Jump(0, 24);
+        let movements = ordering.order(&state, 0, Some(hash_move));
+        assert_eq!(movements.len(), state.movements().count());
+        assert_eq!(movements[0], hash_move);
+        let captures: Vec<i16> = movements[1..]
+            .iter()
+            .map(|m| state.converted_blobs(m))
+            .collect();
+        assert!(captures[0] > 0);
+        assert!(captures.windows(2).all(|w| w[0] >= w[1]));
+    }
+}
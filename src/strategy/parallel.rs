@@ -0,0 +1,167 @@
+//! Parallel alpha - beta (lazy SMP).
+//! All threads search the same position by iterative deepening and share a lock-free
+//! `TranspositionTable`. Helper threads do not communicate otherwise : they fill the table with
+//! bounds and best moves the main thread then uses to prune and order its own search.
+//! Helpers start at different depths and learn their own killers and history so that they do
+//! not all explore the same nodes in the same order.
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread::{available_parallelism, scope};
+
+use super::alphabeta::alpha_beta;
+use super::context::{deepen, SearchContext, SearchLimits, SearchStats};
+use super::tt::TranspositionTable;
+use super::Strategy;
+use crate::configuration::{Configuration, Movement};
+use crate::evaluation::{Evaluator, Material};
+use crate::score::Score;
+
+/// Alpha - Beta on several threads with given maximum number of recursions.
+/// Only the main thread's result is used : once it completes its last iteration (or is stopped)
+/// the helpers are stopped too.
+/// The transposition table of the context is used if any, else our own one (kept between moves).
+pub struct ParallelAlphaBeta<E = Material> {
+    depth: u8,
+    evaluator: E,
+    threads: usize,
+    table: TranspositionTable,
+}
+
+impl<E: Evaluator> fmt::Display for ParallelAlphaBeta<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Parallel Alpha - Beta (max level: {}, {} threads, {})",
+            self.depth, self.threads, self.evaluator
+        )
+    }
+}
+
+impl<E: Evaluator> ParallelAlphaBeta<E> {
+    /// New parallel search with given depth and evaluator, using all available cores.
+    pub fn new(depth: u8, evaluator: E) -> Self {
+        ParallelAlphaBeta {
+            depth,
+            evaluator,
+            threads: available_parallelism().map_or(1, |n| n.get()),
+            table: Default::default(),
+        }
+    }
+
+    /// Sets the number of threads (main thread included).
+    pub fn threads(self, threads: usize) -> Self {
+        ParallelAlphaBeta {
+            threads: threads.max(1),
+            ..self
+        }
+    }
+}
+
+/// Deepen from given depth until stopped, without reporting anything : results only matter
+/// through the table.
+fn help<E: Evaluator + ?Sized>(
+    state: &Configuration,
+    first_depth: u8,
+    max_depth: u8,
+    evaluator: &E,
+    context: &mut SearchContext,
+) -> SearchStats {
+    for depth in first_depth..=max_depth {
+        alpha_beta(depth, 0, state, Score::MIN, Score::MAX, evaluator, context);
+        if context.stopped() {
+            break;
+        }
+    }
+    context.stats()
+}
+
+impl<E: Evaluator + Sync> Strategy for ParallelAlphaBeta<E> {
+    fn compute_next_move(
+        &mut self,
+        state: &Configuration,
+        context: &mut SearchContext,
+    ) -> Option<Movement> {
+        let depth = context.depth(self.depth);
+        let table = match context.table {
+            Some(table) => table,
+            None => {
+                self.table.new_search();
+                &self.table
+            }
+        };
+        // all threads share what remains of the time budget and of the node budget
+        let limits = SearchLimits {
+            time: context.limits.time.map(|t| t.saturating_sub(context.elapsed())),
+            ..context.limits
+        };
+        let nodes = AtomicU64::new(context.nodes());
+        let outer_stop = context.stop_flag();
+        let helpers_stop = AtomicBool::new(false);
+        let evaluator = &self.evaluator;
+        let (movement, stats) = scope(|scope| {
+            let helpers: Vec<_> = (1..self.threads)
+                .map(|helper| {
+                    let (helpers_stop, nodes) = (&helpers_stop, &nodes);
+                    scope.spawn(move || {
+                        let mut context = SearchContext::new(limits)
+                            .with_table(table)
+                            .with_stop(helpers_stop)
+                            .with_shared_nodes(nodes);
+                        help(state, 1 + (helper % 2) as u8, depth, evaluator, &mut context)
+                    })
+                })
+                .collect();
+            let mut main = SearchContext::new(limits)
+                .with_table(table)
+                .with_shared_nodes(&nodes);
+            if let Some(stop) = outer_stop {
+                main = main.with_stop(stop);
+            }
+            let movement = deepen(
+                state,
+                &mut main,
+                depth,
+                |depth, context| {
+                    alpha_beta(depth, 0, state, Score::MIN, Score::MAX, evaluator, context).1
+                },
+                |_| {},
+            );
+            helpers_stop.store(true, Ordering::Relaxed);
+            let mut stats = vec![main.stats()];
+            stats.extend(helpers.into_iter().map(|h| h.join().expect("helper thread panicked")));
+            (movement, stats)
+        });
+        for stats in stats {
+            context.add_stats(stats);
+        }
+        movement
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+    use crate::fixtures::last_blue_blob;
+
+    #[test]
+    fn finds_winning_move_with_helpers() {
+        let board = Board::default();
+        let state = last_blue_blob(&board);
+        let mut strategy = ParallelAlphaBeta::new(4, Material).threads(4);
+        let movement = strategy.compute_next_move(&state, &mut Default::default());
+        assert!(state.play(&movement.unwrap()).game_over());
+    }
+
+    #[test]
+    fn helpers_are_stopped_with_the_main_thread() {
+        let board = Board::default();
+        let state = Configuration::new(&board);
+        let mut strategy = ParallelAlphaBeta::new(30, Material).threads(3);
+        let mut context = SearchContext::new(SearchLimits::default().nodes(20_000));
+        let movement = strategy.compute_next_move(&state, &mut context);
+        assert!(state.check_move(&movement.unwrap()));
+        // each thread may visit one node past the limit before stopping
+        assert!((20_000..=20_003).contains(&context.nodes()));
+    }
+}
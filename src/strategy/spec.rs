@@ -0,0 +1,162 @@
+//! Strategies described by short texts, for command line tools.
+//!
+//! A specification is the name of a strategy followed by parameters separated by ':', all
+//! of them optional :
+//! - `greedy:evaluator`
+//! - `minmax:depth:evaluator`, `alphabeta:depth:evaluator`, `negascout:depth:evaluator` and
+//!   `parallel:depth:evaluator` (parallel alpha - beta)
+//! - `mcts:milliseconds:playout` with `random` or `greedy` playouts
+//! - `iterative:milliseconds:algorithm` with `alphabeta` or `minmax` iterative deepening
+//! - `book:file:specification` plays from the opening book stored in given file, then with
+//!   the strategy of the remaining specification
+//! - `human`
+//!
+//! Evaluators are `material` (by default), `mobility`, `frontier` or `safety`.
+//! For example `alphabeta:5:mobility` or `book:standard.book.json:iterative:500`.
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+use super::{
+    AlphaBeta, Book, BookStrategy, Greedy, Human, IterativeDeepening, IterativeStrategy, Mcts,
+    MinMax, NegaScout, ParallelAlphaBeta, Playout, Strategy,
+};
+use crate::evaluation::{Evaluator, Frontier, Material, Mobility, Safety};
+
+#[derive(Debug)]
+/// Why a specification does not describe a strategy.
+pub enum SpecError {
+    /// No strategy has given name.
+    UnknownStrategy(String),
+    /// No evaluator has given name.
+    UnknownEvaluator(String),
+    /// Given parameter has an invalid value.
+    InvalidParameter(String),
+    /// Given specification has more parameters than its strategy takes.
+    TooManyParameters(String),
+    /// The opening book cannot be loaded.
+    Book(io::Error),
+}
+
+impl fmt::Display for SpecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SpecError::UnknownStrategy(name) => write!(f, "unknown strategy '{}'", name),
+            SpecError::UnknownEvaluator(name) => write!(f, "unknown evaluator '{}'", name),
+            SpecError::InvalidParameter(parameter) => {
+                write!(f, "invalid parameter '{}'", parameter)
+            }
+            SpecError::TooManyParameters(spec) => write!(f, "too many parameters in '{}'", spec),
+            SpecError::Book(error) => write!(f, "failed loading book: {}", error),
+        }
+    }
+}
+
+impl Error for SpecError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SpecError::Book(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Evaluators which can be given in specifications.
+type BoxedEvaluator = Box<dyn Evaluator + Send + Sync>;
+
+fn evaluator(name: Option<&str>) -> Result<BoxedEvaluator, SpecError> {
+    Ok(match name.unwrap_or("material") {
+        "material" => Box::new(Material),
+        "mobility" => Box::new(Mobility),
+        "frontier" => Box::new(Frontier),
+        "safety" => Box::new(Safety),
+        name => return Err(SpecError::UnknownEvaluator(name.to_owned())),
+    })
+}
+
+/// Parse given parameter, using the default value if absent.
+fn parameter<T: FromStr>(parameter: Option<&str>, default: T) -> Result<T, SpecError> {
+    parameter.map_or(Ok(default), |parameter| {
+        parameter
+            .parse()
+            .map_err(|_| SpecError::InvalidParameter(parameter.to_owned()))
+    })
+}
+
+/// Build the strategy described by given specification.
+pub fn parse(spec: &str) -> Result<Box<dyn Strategy>, SpecError> {
+    let mut parameters = spec.trim().split(':');
+    let name = parameters.next().unwrap_or_default();
+    if name == "book" {
+        let path = parameters.next().unwrap_or_default();
+        let book = Book::load(path).map_err(SpecError::Book)?;
+        let inner = parse(&parameters.collect::<Vec<_>>().join(":"))?;
+        return Ok(Box::new(BookStrategy::new(book, inner)));
+    }
+    let (first, second) = (parameters.next(), parameters.next());
+    if parameters.next().is_some() {
+        return Err(SpecError::TooManyParameters(spec.to_owned()));
+    }
+    let strategy: Box<dyn Strategy> = match name {
+        "greedy" if second.is_none() => Box::new(Greedy(evaluator(first)?)),
+        "minmax" => Box::new(MinMax(parameter(first, 3)?, evaluator(second)?)),
+        "alphabeta" => Box::new(AlphaBeta(parameter(first, 4)?, evaluator(second)?)),
+        "negascout" => Box::new(NegaScout(parameter(first, 4)?, evaluator(second)?)),
+        "parallel" => Box::new(ParallelAlphaBeta::new(
+            parameter(first, 4)?,
+            evaluator(second)?,
+        )),
+        "mcts" => Box::new(Mcts::new().duration(parameter(first, 1000)?).playout(
+            match second.unwrap_or("random") {
+                "random" => Playout::Random,
+                "greedy" => Playout::Greedy,
+                playout => return Err(SpecError::InvalidParameter(playout.to_owned())),
+            },
+        )),
+        "iterative" => Box::new(
+            IterativeDeepening::new(match second.unwrap_or("alphabeta") {
+                "alphabeta" => IterativeStrategy::AlphaBeta,
+                "minmax" => IterativeStrategy::MinMax,
+                algorithm => return Err(SpecError::InvalidParameter(algorithm.to_owned())),
+            })
+            .duration(parameter(first, 1000)?),
+        ),
+        "human" if first.is_none() => Box::new(Human()),
+        "greedy" | "human" => return Err(SpecError::TooManyParameters(spec.to_owned())),
+        name => return Err(SpecError::UnknownStrategy(name.to_owned())),
+    };
+    Ok(strategy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn specifications_are_parsed() {
+        let names = [
+            ("greedy", "Greedy (material)"),
+            (
+                "alphabeta:2:mobility",
+                "Alpha - Beta (max level: 2, mobility)",
+            ),
+            ("iterative:300", "AlphaBeta (iterative deepening 300ms)"),
+            ("mcts:50:greedy", "MCTS (Greedy playouts, 50ms)"),
+        ];
+        for (spec, name) in &names {
+            assert_eq!(parse(spec).unwrap().to_string(), *name);
+        }
+        for invalid in &[
+            "",
+            "alphabeta:x",
+            "alphabeta:2:luck",
+            "minmax:2:material:3",
+            "greedy:material:2",
+            "book:missing.json:greedy",
+            "mcts:100:smart",
+        ] {
+            assert!(parse(invalid).is_err(), "{} parsed", invalid);
+        }
+    }
+}
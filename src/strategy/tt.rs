@@ -0,0 +1,384 @@
+//! Fixed size transposition table.
+//! Stores for each searched `Configuration` (identified by its hash) the depth of the search,
+//! the score obtained, which kind of bound this score is and the best move found.
+//!
+//! The table only takes `&self` so it can be shared between iterations, successive moves of
+//! a game or even threads. Each slot stores its key xored with its data, a torn write is
+//! therefore seen as a key mismatch and ignored.
+use crate::configuration::{Configuration, Movement};
+use crate::score::Score;
+use std::cmp::{max, min};
+use std::mem;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+/// Default table size (in megabytes).
+pub const DEFAULT_SIZE: usize = 16;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+/// What the stored score tells us about the real value.
+pub enum Bound {
+    /// Score is the real value.
+    Exact,
+    /// Search failed high : real value is at least the score.
+    Lower,
+    /// Search failed low : real value is at most the score.
+    Upper,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+/// Search result stored in the table.
+pub struct Entry {
+    /// Remaining depth of the search which produced this entry.
+    pub depth: u8,
+    /// Score obtained (from the point of view of the player to move).
+    /// Decisive scores count plies from the stored configuration.
+    pub score: Score,
+    /// Kind of bound the score is.
+    pub bound: Bound,
+    /// Best move found (if any).
+    pub best_move: Option<Movement>,
+}
+
+/// Outcome of a table lookup at the start of a search.
+pub(crate) enum Lookup {
+    /// Stored result settles the search : score (for the player to move) and best move.
+    Cutoff(Score, Option<Movement>),
+    /// We still need to search (maybe with a narrower window), starting with given move.
+    Search(Option<Movement>),
+}
+
+#[derive(Default)]
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+/// Two slots per bucket : first one prefers deep entries, second one is always replaced.
+#[derive(Default)]
+struct Bucket([Slot; 2]);
+
+/// Transposition table with a fixed memory budget.
+pub struct TranspositionTable {
+    buckets: Vec<Bucket>,
+    generation: AtomicU8,
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        TranspositionTable::new(DEFAULT_SIZE)
+    }
+}
+
+// data layout (lowest bits first) :
+// score (16 bits) | depth (8 bits) | bound (2 bits) | move kind (2 bits) | move source (8 bits) |
+// move destination (8 bits) | generation (8 bits) | valid (1 bit)
+const VALID: u64 = 1 << 52;
+
+fn pack(entry: &Entry, generation: u8) -> u64 {
+    let bound = match entry.bound {
+        Bound::Exact => 0,
+        Bound::Lower => 1,
+        Bound::Upper => 2,
+    };
+    let (kind, source, destination) = match entry.best_move {
+        None => (0, 0, 0),
+        Some(Movement::Duplicate(destination)) => (1, 0, destination),
+        Some(Movement::Jump(source, destination)) => (2, source, destination),
+    };
+    u64::from(entry.score.value() as u16)
+        | u64::from(entry.depth) << 16
+        | bound << 24
+        | kind << 26
+        | u64::from(source) << 28
+        | u64::from(destination) << 36
+        | u64::from(generation) << 44
+        | VALID
+}
+
+fn unpack(data: u64) -> (Entry, u8) {
+    let source = (data >> 28) as u8;
+    let destination = (data >> 36) as u8;
+    let best_move = match (data >> 26) & 3 {
+        1 => Some(Movement::Duplicate(destination)),
+        2 => Some(Movement::Jump(source, destination)),
+        _ => None,
+    };
+    let bound = match (data >> 24) & 3 {
+        0 => Bound::Exact,
+        1 => Bound::Lower,
+        _ => Bound::Upper,
+    };
+    let entry = Entry {
+        score: Score::from_value(data as u16 as i16),
+        depth: (data >> 16) as u8,
+        bound,
+        best_move,
+    };
+    (entry, (data >> 44) as u8)
+}
+
+impl Slot {
+    fn load(&self, key: u64) -> Option<(Entry, u8)> {
+        let data = self.data.load(Ordering::Relaxed);
+        if data & VALID != 0 && self.key.load(Ordering::Relaxed) ^ data == key {
+            Some(unpack(data))
+        } else {
+            None
+        }
+    }
+
+    /// Content of the slot, whatever its key.
+    fn peek(&self) -> Option<(Entry, u8)> {
+        let data = self.data.load(Ordering::Relaxed);
+        if data & VALID != 0 {
+            Some(unpack(data))
+        } else {
+            None
+        }
+    }
+
+    fn save(&self, key: u64, data: u64) {
+        self.key.store(key ^ data, Ordering::Relaxed);
+        self.data.store(data, Ordering::Relaxed);
+    }
+}
+
+impl TranspositionTable {
+    /// Create a new table using at most the given number of megabytes.
+    pub fn new(megabytes: usize) -> Self {
+        let wanted = (megabytes << 20) / mem::size_of::<Bucket>();
+        // round down to a power of two so that indexing is a simple mask
+        let size = if wanted <= 1 {
+            1
+        } else {
+            1 << (usize::BITS - 1 - wanted.leading_zeros())
+        };
+        TranspositionTable {
+            buckets: (0..size).map(|_| Default::default()).collect(),
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    fn bucket(&self, key: u64) -> &Bucket {
+        &self.buckets[key as usize & (self.buckets.len() - 1)]
+    }
+
+    /// Return what we know about the configuration with given hash.
+    pub fn probe(&self, key: u64) -> Option<Entry> {
+        let bucket = self.bucket(key);
+        bucket
+            .0
+            .iter()
+            .find_map(|slot| slot.load(key))
+            .map(|(entry, _)| entry)
+    }
+
+    /// Store given search result for the configuration with given hash.
+    /// An entry for the same configuration is always overwritten. Otherwise we replace the
+    /// deep entry if it comes from an older search or is not deeper than the new one, and
+    /// the second slot if not.
+    pub fn store(&self, key: u64, entry: Entry) {
+        let generation = self.generation.load(Ordering::Relaxed);
+        let data = pack(&entry, generation);
+        let [deep, always] = &self.bucket(key).0;
+        if always.load(key).is_some() {
+            always.save(key, data);
+            return;
+        }
+        let replace_deep = deep.load(key).is_some()
+            || deep.peek().is_none_or(|(stored, stored_generation)| {
+                stored_generation != generation || stored.depth <= entry.depth
+            });
+        if replace_deep {
+            deep.save(key, data);
+        } else {
+            always.save(key, data);
+        }
+    }
+
+    /// Look for given configuration before searching it at given depth and ply with given
+    /// window. Deep enough bounds narrow the window.
+    pub(crate) fn lookup(
+        &self,
+        state: &Configuration,
+        depth: u8,
+        ply: usize,
+        alpha: &mut Score,
+        beta: &mut Score,
+    ) -> Lookup {
+        self.lookup_if(state, ply, alpha, beta, |stored| stored >= depth)
+    }
+
+    /// Same as `lookup` but only bounds of searches at exactly given depth are used, for
+    /// searches whose results depend on the exact remaining depth (like up to a ply limit).
+    pub(crate) fn lookup_same_depth(
+        &self,
+        state: &Configuration,
+        depth: u8,
+        ply: usize,
+        alpha: &mut Score,
+        beta: &mut Score,
+    ) -> Lookup {
+        self.lookup_if(state, ply, alpha, beta, |stored| stored == depth)
+    }
+
+    /// Look for given configuration, using its bounds if `usable` accepts their depth.
+    fn lookup_if<U: Fn(u8) -> bool>(
+        &self,
+        state: &Configuration,
+        ply: usize,
+        alpha: &mut Score,
+        beta: &mut Score,
+        usable: U,
+    ) -> Lookup {
+        let entry = match self.probe(state.hash()) {
+            Some(entry) => entry,
+            None => return Lookup::Search(None),
+        };
+        if usable(entry.depth) {
+            let score = entry.score.to_root(ply);
+            match entry.bound {
+                Bound::Exact => return Lookup::Cutoff(score, entry.best_move),
+                Bound::Lower => *alpha = max(*alpha, score),
+                Bound::Upper => *beta = min(*beta, score),
+            }
+            if *alpha >= *beta {
+                return Lookup::Cutoff(score, entry.best_move);
+            }
+        }
+        // hash collisions are rare but possible : check the move before playing it
+        Lookup::Search(entry.best_move.filter(|m| state.check_move(m)))
+    }
+
+    /// Record the result of searching given configuration (at given ply) with window
+    /// (alpha, beta).
+    pub(crate) fn record(
+        &self,
+        state: &Configuration,
+        depth: u8,
+        ply: usize,
+        (alpha, beta): (Score, Score),
+        score: Score,
+        best_move: Option<Movement>,
+    ) {
+        let bound = if score <= alpha {
+            Bound::Upper
+        } else if score >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.store(
+            state.hash(),
+            Entry {
+                depth,
+                score: score.to_node(ply),
+                bound,
+                best_move,
+            },
+        );
+    }
+
+    /// Signal the start of a new search (on a new move).
+    /// Entries from previous searches are then replaced first.
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Forget everything.
+    pub fn clear(&mut self) {
+        for slot in self.buckets.iter_mut().flat_map(|b| b.0.iter_mut()) {
+            *slot = Default::default();
+        }
+        self.generation = AtomicU8::new(0);
+    }
+
+    /// Number of configurations the table can hold.
+    pub fn capacity(&self) -> usize {
+        self.buckets.len() * 2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+
+    fn entry(depth: u8, score: i16, best_move: Option<Movement>) -> Entry {
+        Entry {
+            depth,
+            score: Score::from_value(score),
+            bound: Bound::Lower,
+            best_move,
+        }
+    }
+
+    #[test]
+    fn entries_round_trip() {
+        let table = TranspositionTable::new(1);
+        let stored = [
+            entry(3, -64, Some(Movement::Jump(63, 45))),
+            entry(255, Score::win_in(3).value(), Some(Movement::Duplicate(0))),
+            entry(0, Score::MIN.value(), None),
+        ];
+        for (key, e) in stored.iter().enumerate() {
+            table.store(key as u64 * 7919, *e);
+        }
+        for (key, e) in stored.iter().enumerate() {
+            assert_eq!(table.probe(key as u64 * 7919), Some(*e));
+        }
+        assert_eq!(table.probe(12), None);
+    }
+
+    #[test]
+    fn decisive_scores_are_stored_relative_to_the_node() {
+        let board = Board::default();
+        let state = Configuration::new(&board);
+        let table = TranspositionTable::new(1);
+        let window = (Score::MIN, Score::MAX);
+        table.record(&state, 4, 2, window, Score::win_in(5), None);
+        assert_eq!(table.probe(state.hash()).unwrap().score, Score::win_in(3));
+        let (mut alpha, mut beta) = window;
+        match table.lookup(&state, 4, 6, &mut alpha, &mut beta) {
+            Lookup::Cutoff(score, _) => assert_eq!(score, Score::win_in(9)),
+            Lookup::Search(_) => panic!("exact entry should cut"),
+        }
+    }
+
+    #[test]
+    fn same_depth_lookups_ignore_deeper_entries() {
+        let board = Board::default();
+        let state = Configuration::new(&board);
+        let table = TranspositionTable::new(1);
+        let window = (Score::MIN, Score::MAX);
+        table.record(&state, 6, 0, window, Score::from_value(2), None);
+        let cuts = |lookup| matches!(lookup, Lookup::Cutoff(..));
+        let (mut alpha, mut beta) = window;
+        assert!(cuts(table.lookup(&state, 4, 0, &mut alpha, &mut beta)));
+        let (mut alpha, mut beta) = window;
+        assert!(!cuts(
+            table.lookup_same_depth(&state, 4, 0, &mut alpha, &mut beta)
+        ));
+        assert_eq!((alpha, beta), window);
+        assert!(cuts(
+            table.lookup_same_depth(&state, 6, 0, &mut alpha, &mut beta)
+        ));
+    }
+
+    #[test]
+    fn deep_entries_are_kept() {
+        let table = TranspositionTable::new(1);
+        let buckets = table.capacity() as u64 / 2;
+        table.store(1, entry(8, 1, None));
+        table.store(1 + buckets, entry(2, 2, None));
+        table.store(1 + 2 * buckets, entry(3, 3, None));
+        assert_eq!(table.probe(1), Some(entry(8, 1, None)));
+        assert_eq!(table.probe(1 + buckets), None);
+        assert_eq!(table.probe(1 + 2 * buckets), Some(entry(3, 3, None)));
+        // a new search can replace the old deep entry
+        table.new_search();
+        table.store(1 + buckets, entry(2, 2, None));
+        assert_eq!(table.probe(1), None);
+        assert_eq!(table.probe(1 + buckets), Some(entry(2, 2, None)));
+    }
+}
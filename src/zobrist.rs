@@ -0,0 +1,51 @@
+//! Zobrist keys used to hash `Configuration`s.
+//! Each (player, position) pair gets a fixed pseudo-random key and the hash of a configuration
+//! is the xor of the keys of all its blobs (plus a key for the player to move).
+//! Since xor is its own inverse, hashes can be updated incrementally when playing moves.
+use super::positions::{Position, Positions, CELLS};
+
+/// Key xored in when blue is the player to move.
+pub(crate) const PLAYER_KEY: u64 = splitmix64(2 * CELLS as u64 + 1);
+
+/// Keys for blobs of each player on each position.
+pub(crate) const BLOB_KEYS: [[u64; CELLS]; 2] = blob_keys();
+
+/// One step of the splitmix64 generator : good enough (and deterministic) random keys.
+const fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+const fn blob_keys() -> [[u64; CELLS]; 2] {
+    let mut keys = [[0; CELLS]; 2];
+    let mut player = 0;
+    while player < 2 {
+        let mut position = 0;
+        while position < CELLS {
+            keys[player][position] = splitmix64((player * CELLS + position + 1) as u64);
+            position += 1;
+        }
+        player += 1;
+    }
+    keys
+}
+
+/// Key of given player's blob on given `Position`.
+pub(crate) fn blob_key(player: usize, position: Position) -> u64 {
+    BLOB_KEYS[player][position as usize]
+}
+
+/// Xor of the keys of all given player's blobs at given `Positions`.
+pub(crate) fn blobs_key(player: usize, blobs: Positions) -> u64 {
+    blobs
+        .positions()
+        .fold(0, |key, position| key ^ blob_key(player, position))
+}
+
+/// Compute from scratch the hash of given blobs with given player to move.
+pub(crate) fn hash(blobs: &[Positions; 2], current_player: bool) -> u64 {
+    let player_key = if current_player { PLAYER_KEY } else { 0 };
+    blobs_key(0, blobs[0]) ^ blobs_key(1, blobs[1]) ^ player_key
+}